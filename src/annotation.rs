@@ -0,0 +1,869 @@
+use std::{
+    borrow::Cow,
+    collections::{btree_map, BTreeMap},
+    fmt::Display,
+    iter::Peekable,
+    str::FromStr,
+};
+
+use crate::translator::{Strand, Translator};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub category: String,
+    pub systematic_name: String,
+    pub standard_name: String,
+    pub qualifier: String,
+    pub isoform: String,
+    pub strand: Strand,
+}
+
+impl Label {
+    /// Unique key of the labelled model: the systematic name, qualified by
+    /// the isoform when one is set.
+    pub fn id(&self) -> String {
+        if self.isoform.is_empty() {
+            self.systematic_name.clone()
+        } else {
+            format!("{}#{}", self.systematic_name, self.isoform)
+        }
+    }
+}
+
+/// Names of the columns written for every track, in order.
+pub const TRACK_COLUMNS: [&str; 6] = [
+    "Type",
+    "Subtype",
+    "Systematic name",
+    "Standard name",
+    "Qualifier",
+    "Isoform",
+];
+
+/// Part of a feature a position belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Subtype {
+    /// Inside a feature without a more specific annotation.
+    Unknown,
+    Utr5,
+    Utr3,
+    /// A 5' UTR guessed for a gene without observed UTRs.
+    InferredUtr5,
+    InferredUtr3,
+    Exon,
+    Intron,
+}
+
+impl Display for Subtype {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Subtype {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unknown => "?",
+            Self::Utr5 => "UTR 5'",
+            Self::Utr3 => "UTR 3'",
+            Self::InferredUtr5 => "UTR 5' inferred",
+            Self::InferredUtr3 => "UTR 3' inferred",
+            Self::Exon => "Exon",
+            Self::Intron => "Intron",
+        }
+    }
+
+    /// The subtype without the `inferred` mark.
+    fn observed(&self) -> Self {
+        match self {
+            Self::InferredUtr5 => Self::Utr5,
+            Self::InferredUtr3 => Self::Utr3,
+            _ => *self,
+        }
+    }
+}
+
+impl FromStr for Subtype {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unknown" => Ok(Self::Unknown),
+            "utr5" => Ok(Self::Utr5),
+            "utr3" => Ok(Self::Utr3),
+            "exon" => Ok(Self::Exon),
+            "intron" => Ok(Self::Intron),
+            _ => Err(format!("Invalid subtype: {}", s)),
+        }
+    }
+}
+
+/// Subtypes from the highest to the lowest priority. Subtypes missing from
+/// the list rank below the listed ones, in their default order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubtypePrecedence(Vec<Subtype>);
+
+impl SubtypePrecedence {
+    const DEFAULT: [Subtype; 5] = [
+        Subtype::Intron,
+        Subtype::Exon,
+        Subtype::Utr3,
+        Subtype::Utr5,
+        Subtype::Unknown,
+    ];
+
+    pub fn new(order: Vec<Subtype>) -> Self {
+        let mut order = order;
+        for subtype in Self::DEFAULT {
+            if !order.contains(&subtype) {
+                order.push(subtype);
+            }
+        }
+        Self(order)
+    }
+
+    fn rank(&self, subtype: Subtype) -> usize {
+        self.0
+            .iter()
+            .position(|s| *s == subtype.observed())
+            .unwrap()
+    }
+}
+
+impl Default for SubtypePrecedence {
+    /// Introns over exons over 3' and 5' UTRs, matching the order in which
+    /// the annotator used to paint them.
+    fn default() -> Self {
+        Self(Self::DEFAULT.to_vec())
+    }
+}
+
+impl FromStr for SubtypePrecedence {
+    type Err = String;
+
+    /// Parses a comma-separated list such as `exon,utr5,utr3,intron`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let order = s
+            .split(',')
+            .map(|name| name.trim().parse())
+            .collect::<Result<Vec<Subtype>, _>>()?;
+        Ok(Self::new(order))
+    }
+}
+
+/// How the subtype column is filled when several subtypes of one feature
+/// cover a position.
+#[derive(Debug, Clone, Default)]
+pub struct SubtypeRules {
+    pub precedence: SubtypePrecedence,
+    /// Write every applicable subtype separated by `;` instead of the one
+    /// with the highest precedence.
+    pub all_subtypes: bool,
+    /// Add a `Conflict` column marking positions with more than one specific
+    /// subtype, e.g. an exon overlapping a UTR.
+    pub flag_conflicts: bool,
+}
+
+/// Names of the optional columns locating a track's feature position on the
+/// genome.
+pub const COORDINATE_COLUMNS: [&str; 4] =
+    ["Chromosome", "Position", "Reference base", "Sense base"];
+
+/// Names of the optional columns locating a position within the transcript
+/// of a track's feature.
+pub const TRANSCRIPT_COLUMNS: [&str; 4] = [
+    "Transcript position",
+    "Start codon offset",
+    "Stop codon offset",
+    "Element",
+];
+
+/// Exonic blocks and CDS of a transcript in input index space, used to
+/// express positions relative to the mature transcript.
+#[derive(Debug, Clone)]
+pub struct TranscriptStructure {
+    strand: Strand,
+    /// Sorted, disjoint blocks of the spliced transcript.
+    blocks: Vec<(usize, usize)>,
+    /// Number of block nucleotides before each block, in genome order.
+    preceding: Vec<usize>,
+    length: usize,
+    cds: Option<(usize, usize)>,
+}
+
+/// Where a position lies within a transcript.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TranscriptPosition {
+    /// In exon `number`, at 0-based `index` of the spliced transcript.
+    Exon {
+        number: usize,
+        index: usize,
+    },
+    Intron {
+        number: usize,
+    },
+}
+
+impl TranscriptStructure {
+    /// `blocks` must be sorted and disjoint, as given by
+    /// [`FeatureModel::blocks`](crate::model::FeatureModel::blocks); bases
+    /// shared by several blocks would shift every later position.
+    pub fn new(strand: Strand, blocks: Vec<(usize, usize)>, cds: Option<(usize, usize)>) -> Self {
+        debug_assert!(blocks.windows(2).all(|pair| pair[0].1 < pair[1].0));
+        let mut preceding = Vec::with_capacity(blocks.len());
+        let mut length = 0;
+        for (start, end) in &blocks {
+            preceding.push(length);
+            length += end - start + 1;
+        }
+        Self {
+            strand,
+            blocks,
+            preceding,
+            length,
+            cds,
+        }
+    }
+
+    /// Locates `position` in the transcript. Exons and introns are numbered
+    /// from the 5' end, starting at 1.
+    pub fn locate(&self, position: usize) -> Option<TranscriptPosition> {
+        let k = self
+            .blocks
+            .partition_point(|(start, _)| *start <= position)
+            .checked_sub(1)?;
+        let (start, end) = self.blocks[k];
+        let count = self.blocks.len();
+        if position <= end {
+            let offset = self.preceding[k] + position - start;
+            Some(match self.strand {
+                Strand::Plus => TranscriptPosition::Exon {
+                    number: k + 1,
+                    index: offset,
+                },
+                Strand::Minus => TranscriptPosition::Exon {
+                    number: count - k,
+                    index: self.length - 1 - offset,
+                },
+            })
+        } else if k + 1 < count {
+            Some(TranscriptPosition::Intron {
+                number: match self.strand {
+                    Strand::Plus => k + 1,
+                    Strand::Minus => count - 1 - k,
+                },
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Input index of the nucleotide at 0-based `index` of the spliced
+    /// transcript.
+    pub fn position(&self, index: usize) -> Option<usize> {
+        if index >= self.length {
+            return None;
+        }
+        let offset = match self.strand {
+            Strand::Plus => index,
+            Strand::Minus => self.length - 1 - index,
+        };
+        let k = self.preceding.partition_point(|p| *p <= offset) - 1;
+        Some(self.blocks[k].0 + offset - self.preceding[k])
+    }
+
+    fn index(&self, position: usize) -> Option<usize> {
+        match self.locate(position)? {
+            TranscriptPosition::Exon { index, .. } => Some(index),
+            TranscriptPosition::Intron { .. } => None,
+        }
+    }
+
+    /// Transcript indices of the first nucleotide of the start codon and the
+    /// last nucleotide of the stop codon.
+    fn codons(&self) -> Option<(usize, usize)> {
+        let (first, last) = self.cds?;
+        let (first, last) = (self.index(first)?, self.index(last)?);
+        Some((first.min(last), first.max(last)))
+    }
+
+    /// Offsets of `position` from the start codon (1 at its first nucleotide,
+    /// negative in the 5' UTR) and from the stop codon (-1 at its last
+    /// nucleotide, positive in the 3' UTR).
+    pub fn codon_offsets(&self, position: usize) -> Option<(isize, isize)> {
+        let index = self.index(position)? as isize;
+        let (start, stop) = self.codons()?;
+        let from_start = index - start as isize;
+        let from_stop = index - stop as isize;
+        Some((
+            if from_start >= 0 {
+                from_start + 1
+            } else {
+                from_start
+            },
+            if from_stop > 0 {
+                from_stop
+            } else {
+                from_stop - 1
+            },
+        ))
+    }
+}
+
+/// Maps input indices back to chromosome positions and bases.
+#[derive(Debug, Clone)]
+pub struct GenomeCoordinates {
+    sequence: String,
+    /// `(offset, length, name)` of every chromosome, ordered by offset.
+    chromosomes: Vec<(usize, usize, String)>,
+}
+
+impl GenomeCoordinates {
+    /// Takes the chromosome layout from `translator` and the bases from the
+    /// input `sequence`.
+    pub fn new(translator: &Translator, sequence: String) -> Self {
+        let chromosomes = translator
+            .chromosomes()
+            .into_iter()
+            .map(|(chromosome, offset, length)| (offset, length, format!("chr{}", chromosome)))
+            .collect();
+        Self {
+            sequence,
+            chromosomes,
+        }
+    }
+
+    fn locate(&self, index: usize) -> Option<(&str, usize)> {
+        let i = self
+            .chromosomes
+            .partition_point(|(offset, _, _)| *offset <= index)
+            .checked_sub(1)?;
+        let (offset, length, name) = &self.chromosomes[i];
+        (index < offset + length).then(|| (name.as_str(), index - offset + 1))
+    }
+
+    fn base(&self, index: usize) -> &str {
+        self.sequence.get(index..index + 1).unwrap_or("")
+    }
+}
+
+pub(crate) fn complement(base: &str) -> &str {
+    match base {
+        "A" => "U",
+        "U" => "A",
+        "C" => "G",
+        "G" => "C",
+        _ => base,
+    }
+}
+
+/// Names of the columns describing positions outside of features, written
+/// once after all tracks.
+pub const REGION_COLUMNS: [&str; 6] = [
+    "Region",
+    "Upstream gene",
+    "Upstream distance",
+    "Downstream gene",
+    "Downstream distance",
+    "Promoter",
+];
+
+/// A closed interval `start..=end` painted onto a layer. When several painted
+/// intervals cover the same position, the one painted last wins.
+#[derive(Debug, Clone)]
+struct Painted<T> {
+    start: usize,
+    end: usize,
+    order: usize,
+    value: T,
+}
+
+/// Painted intervals grouped by their start, each group in painting order.
+#[derive(Debug, Clone)]
+struct Layer<T> {
+    intervals: BTreeMap<usize, Vec<Painted<T>>>,
+}
+
+impl<T> Layer<T> {
+    fn new() -> Self {
+        Self {
+            intervals: BTreeMap::new(),
+        }
+    }
+
+    fn paint(&mut self, start: usize, end: usize, order: usize, value: T) {
+        self.intervals.entry(start).or_default().push(Painted {
+            start,
+            end,
+            order,
+            value,
+        });
+    }
+
+    fn extend(&mut self, other: Layer<T>, order_offset: usize, map: impl Fn(T) -> T) {
+        for p in other.intervals.into_values().flatten() {
+            self.paint(p.start, p.end, p.order + order_offset, map(p.value));
+        }
+    }
+
+    fn cursor(&self) -> LayerCursor<'_, T> {
+        LayerCursor {
+            pending: self.intervals.iter().peekable(),
+            active: Vec::new(),
+        }
+    }
+}
+
+/// Sweeps a layer from left to right. Positions must be queried in
+/// non-decreasing order.
+struct LayerCursor<'a, T> {
+    pending: Peekable<btree_map::Iter<'a, usize, Vec<Painted<T>>>>,
+    active: Vec<&'a Painted<T>>,
+}
+
+impl<'a, T> LayerCursor<'a, T> {
+    fn advance(&mut self, position: usize) {
+        self.active.retain(|p| p.end >= position);
+        while let Some((_, group)) = self.pending.next_if(|(start, _)| **start <= position) {
+            self.active
+                .extend(group.iter().filter(|painted| painted.end >= position));
+        }
+    }
+
+    /// All values covering `position`.
+    fn all_at(&mut self, position: usize) -> impl Iterator<Item = &'a T> + '_ {
+        self.advance(position);
+        self.active.iter().map(|p| &p.value)
+    }
+
+    /// The value painted last of those covering `position`.
+    fn at(&mut self, position: usize) -> Option<&'a T> {
+        self.advance(position);
+        self.active.iter().max_by_key(|p| p.order).map(|p| &p.value)
+    }
+}
+
+/// One set of non-overlapping features (as decided by the layout) together
+/// with their subtypes, stored as interval lists instead of per-nucleotide
+/// cells. Subtypes remember the feature they belong to.
+#[derive(Debug, Clone)]
+pub struct Track {
+    labels: Vec<Label>,
+    structures: Vec<Option<TranscriptStructure>>,
+    features: Layer<usize>,
+    subtypes: Layer<(usize, Subtype)>,
+    next_order: usize,
+}
+
+impl Track {
+    fn new() -> Self {
+        Self {
+            labels: Vec::new(),
+            structures: Vec::new(),
+            features: Layer::new(),
+            subtypes: Layer::new(),
+            next_order: 0,
+        }
+    }
+
+    pub fn add_feature(&mut self, start: usize, end: usize, label: Label) {
+        let index = self.labels.len();
+        self.labels.push(label);
+        self.structures.push(None);
+        self.features.paint(start, end, self.next_order, index);
+        self.next_order += 1;
+    }
+
+    /// Marks `start..=end` of the feature added last as `subtype`.
+    pub fn set_subtype(&mut self, start: usize, end: usize, subtype: Subtype) {
+        let feature = self.labels.len().saturating_sub(1);
+        self.subtypes
+            .paint(start, end, self.next_order, (feature, subtype));
+        self.next_order += 1;
+    }
+
+    /// Sets the exon layout of the feature added last.
+    pub fn set_structure(&mut self, structure: TranscriptStructure) {
+        if let Some(last) = self.structures.last_mut() {
+            *last = Some(structure);
+        }
+    }
+
+    /// Moves all features and subtypes of `other` into this track. They are
+    /// painted after the existing ones.
+    pub fn extend(&mut self, other: Track) {
+        let label_offset = self.labels.len();
+        let order_offset = self.next_order;
+        self.labels.extend(other.labels);
+        self.structures.extend(other.structures);
+        self.features
+            .extend(other.features, order_offset, |i| i + label_offset);
+        self.subtypes
+            .extend(other.subtypes, order_offset, |(i, s)| (i + label_offset, s));
+        self.next_order += other.next_order;
+    }
+
+    pub fn cursor(&self) -> TrackCursor<'_> {
+        TrackCursor {
+            labels: &self.labels,
+            structures: &self.structures,
+            features: self.features.cursor(),
+            subtypes: self.subtypes.cursor(),
+        }
+    }
+}
+
+pub struct TrackCursor<'a> {
+    labels: &'a [Label],
+    structures: &'a [Option<TranscriptStructure>],
+    features: LayerCursor<'a, usize>,
+    subtypes: LayerCursor<'a, (usize, Subtype)>,
+}
+
+impl<'a> TrackCursor<'a> {
+    /// The feature at `position`, its exon layout and its subtypes there,
+    /// ordered by `precedence`.
+    pub fn at(
+        &mut self,
+        position: usize,
+        precedence: &SubtypePrecedence,
+    ) -> (
+        Option<&'a Label>,
+        Option<&'a TranscriptStructure>,
+        Vec<Subtype>,
+    ) {
+        let feature = self.features.at(position).copied();
+        let mut subtypes: Vec<Subtype> = self
+            .subtypes
+            .all_at(position)
+            .filter(|(i, _)| Some(*i) == feature)
+            .map(|(_, s)| *s)
+            .collect();
+        subtypes.sort_by_key(|s| precedence.rank(*s));
+        subtypes.dedup();
+        (
+            feature.map(|i| &self.labels[i]),
+            feature.and_then(|i| self.structures[i].as_ref()),
+            subtypes,
+        )
+    }
+}
+
+/// Features flanking an intergenic interval, each with the input index of its
+/// nucleotide closest to the interval. Upstream features have the interval on
+/// their 3' side, downstream features on their 5' side.
+#[derive(Debug, Clone, Default)]
+struct Gap {
+    upstream: Vec<(String, usize)>,
+    downstream: Vec<(String, usize)>,
+}
+
+/// Intergenic intervals with their neighbouring features and promoter
+/// windows upstream of transcript starts.
+#[derive(Debug, Clone)]
+pub struct Regions {
+    gaps: Layer<Gap>,
+    promoters: Layer<String>,
+}
+
+impl Default for Regions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Regions {
+    pub fn new() -> Self {
+        Self {
+            gaps: Layer::new(),
+            promoters: Layer::new(),
+        }
+    }
+
+    /// Marks `start..=end` as intergenic. Neighbours are given by name, the
+    /// input index of their nucleotide next to the gap and their strand;
+    /// `before` lies at lower positions and `after` at higher ones. Each is
+    /// reported upstream or downstream of the gap along its own strand.
+    pub fn add_gap(
+        &mut self,
+        start: usize,
+        end: usize,
+        before: Option<(&str, usize, Strand)>,
+        after: Option<(&str, usize, Strand)>,
+    ) {
+        let mut gap = Gap::default();
+        for (neighbour, is_before) in [(before, true), (after, false)] {
+            let Some((name, index, strand)) = neighbour else {
+                continue;
+            };
+            let side = if is_before == (strand == Strand::Plus) {
+                &mut gap.upstream
+            } else {
+                &mut gap.downstream
+            };
+            side.push((name.to_string(), index));
+        }
+        self.gaps.paint(start, end, 0, gap);
+    }
+
+    pub fn add_promoter(&mut self, start: usize, end: usize, name: &str) {
+        self.promoters.paint(start, end, 0, name.to_string());
+    }
+
+    fn cursor(&self) -> RegionCursor<'_> {
+        RegionCursor {
+            gaps: self.gaps.cursor(),
+            promoters: self.promoters.cursor(),
+        }
+    }
+}
+
+struct RegionCursor<'a> {
+    gaps: LayerCursor<'a, Gap>,
+    promoters: LayerCursor<'a, String>,
+}
+
+impl<'a> RegionCursor<'a> {
+    /// Appends the [`REGION_COLUMNS`] at `position` to `row`.
+    fn extend_row(&mut self, position: usize, row: &mut Vec<Cow<'a, str>>) {
+        match self.gaps.at(position) {
+            Some(gap) => {
+                row.push(Cow::Borrowed("Intergenic"));
+                for neighbours in [&gap.upstream, &gap.downstream] {
+                    let names: Vec<&str> =
+                        neighbours.iter().map(|(name, _)| name.as_str()).collect();
+                    let distances: Vec<String> = neighbours
+                        .iter()
+                        .map(|(_, index)| position.abs_diff(*index).to_string())
+                        .collect();
+                    row.push(Cow::Owned(names.join(";")));
+                    row.push(Cow::Owned(distances.join(";")));
+                }
+            }
+            None => row.extend((0..5).map(|_| Cow::Borrowed(""))),
+        }
+
+        let mut promoters: Vec<&str> = Vec::new();
+        for name in self.promoters.all_at(position) {
+            if !promoters.contains(&name.as_str()) {
+                promoters.push(name);
+            }
+        }
+        row.push(match promoters.len() {
+            0 => Cow::Borrowed(""),
+            1 => Cow::Borrowed(promoters[0]),
+            _ => Cow::Owned(promoters.join(";")),
+        });
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Annotations {
+    tracks: Vec<Track>,
+    rules: SubtypeRules,
+    regions: Option<Regions>,
+    coordinates: Option<GenomeCoordinates>,
+    transcript_columns: bool,
+}
+
+impl Annotations {
+    pub fn new(track_count: usize) -> Self {
+        Self {
+            tracks: (0..track_count).map(|_| Track::new()).collect(),
+            rules: SubtypeRules::default(),
+            regions: None,
+            coordinates: None,
+            transcript_columns: false,
+        }
+    }
+
+    pub fn set_subtype_rules(&mut self, rules: SubtypeRules) {
+        self.rules = rules;
+    }
+
+    /// Adds the [`COORDINATE_COLUMNS`] to every track.
+    pub fn set_coordinates(&mut self, coordinates: GenomeCoordinates) {
+        self.coordinates = Some(coordinates);
+    }
+
+    /// Adds the [`TRANSCRIPT_COLUMNS`] to every track.
+    pub fn set_transcript_columns(&mut self, enabled: bool) {
+        self.transcript_columns = enabled;
+    }
+
+    /// Names of the columns written for every track, in order.
+    pub fn track_columns(&self) -> Vec<&'static str> {
+        let mut columns = TRACK_COLUMNS.to_vec();
+        if self.rules.flag_conflicts {
+            columns.push("Conflict");
+        }
+        if self.coordinates.is_some() {
+            columns.extend(COORDINATE_COLUMNS);
+        }
+        if self.transcript_columns {
+            columns.extend(TRANSCRIPT_COLUMNS);
+        }
+        columns
+    }
+
+    /// Adds the [`REGION_COLUMNS`] to every row.
+    pub fn set_regions(&mut self, regions: Regions) {
+        self.regions = Some(regions);
+    }
+
+    pub fn track_count(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Header names of all columns produced by [`AnnotationCursor::row`].
+    pub fn column_names(&self) -> Vec<String> {
+        let columns = self.track_columns();
+        let mut names: Vec<String> = (1..=self.tracks.len())
+            .flat_map(|j| columns.iter().map(move |c| format!("{} {}", c, j)))
+            .collect();
+        if self.regions.is_some() {
+            names.extend(REGION_COLUMNS.iter().map(|c| c.to_string()));
+        }
+        names
+    }
+
+    pub fn track_mut(&mut self, index: usize) -> &mut Track {
+        &mut self.tracks[index]
+    }
+
+    /// Combines annotations computed independently (e.g. per chromosome) into
+    /// one set with as many tracks as the widest of them.
+    pub fn merge(parts: Vec<Annotations>) -> Self {
+        let track_count = parts.iter().map(|a| a.track_count()).max().unwrap_or(0);
+        let mut merged = Annotations::new(track_count);
+        for part in parts {
+            for (i, track) in part.tracks.into_iter().enumerate() {
+                merged.tracks[i].extend(track);
+            }
+        }
+        merged
+    }
+
+    pub fn cursor(&self) -> AnnotationCursor<'_> {
+        AnnotationCursor {
+            tracks: self.tracks.iter().map(|t| t.cursor()).collect(),
+            width: self.track_columns().len(),
+            rules: &self.rules,
+            regions: self.regions.as_ref().map(|r| r.cursor()),
+            coordinates: self.coordinates.as_ref(),
+            transcript_columns: self.transcript_columns,
+        }
+    }
+}
+
+/// A feature covering a position, as returned by
+/// [`AnnotationCursor::features`].
+pub type FeatureAt<'a> = (&'a Label, Option<&'a TranscriptStructure>, Vec<Subtype>);
+
+/// Streams the annotation of consecutive positions, one row at a time.
+pub struct AnnotationCursor<'a> {
+    tracks: Vec<TrackCursor<'a>>,
+    width: usize,
+    rules: &'a SubtypeRules,
+    regions: Option<RegionCursor<'a>>,
+    coordinates: Option<&'a GenomeCoordinates>,
+    transcript_columns: bool,
+}
+
+impl<'a> AnnotationCursor<'a> {
+    /// The feature of every track covering `position` together with its exon
+    /// layout and its subtypes there, ordered by precedence.
+    pub fn features(&mut self, position: usize) -> Vec<FeatureAt<'a>> {
+        let precedence = &self.rules.precedence;
+        self.tracks
+            .iter_mut()
+            .filter_map(|track| {
+                let (label, structure, subtypes) = track.at(position, precedence);
+                label.map(|label| (label, structure, subtypes))
+            })
+            .collect()
+    }
+
+    /// Returns the [`Annotations::track_columns`] of every track at
+    /// `position`, followed by the [`REGION_COLUMNS`] when regions are set.
+    pub fn row(&mut self, position: usize) -> Vec<Cow<'a, str>> {
+        let location = self.coordinates.and_then(|c| c.locate(position));
+        let mut row = Vec::with_capacity(self.tracks.len() * self.width);
+        for track in self.tracks.iter_mut() {
+            let (label, structure, subtypes) = track.at(position, &self.rules.precedence);
+            // `?` only says that nothing more specific is known.
+            let specific: Vec<Subtype> = subtypes
+                .iter()
+                .copied()
+                .filter(|s| *s != Subtype::Unknown)
+                .collect();
+            let subtype = match (self.rules.all_subtypes, specific.len()) {
+                (true, n) if n > 1 => Cow::Owned(
+                    specific
+                        .iter()
+                        .map(|s| s.as_str())
+                        .collect::<Vec<_>>()
+                        .join(";"),
+                ),
+                _ => Cow::Borrowed(subtypes.first().map_or("", |s| s.as_str())),
+            };
+
+            let field = |f: fn(&Label) -> &str| Cow::Borrowed(label.map_or("", f));
+            row.push(field(|l| &l.category));
+            row.push(subtype);
+            row.push(field(|l| &l.systematic_name));
+            row.push(field(|l| &l.standard_name));
+            row.push(field(|l| &l.qualifier));
+            row.push(field(|l| &l.isoform));
+            if self.rules.flag_conflicts {
+                row.push(Cow::Borrowed(if specific.len() > 1 { "yes" } else { "" }));
+            }
+            if let Some(coordinates) = self.coordinates {
+                match (label, location) {
+                    (Some(label), Some((chromosome, genomic))) => {
+                        let base = coordinates.base(position);
+                        let sense = match label.strand {
+                            Strand::Plus => base,
+                            Strand::Minus => complement(base),
+                        };
+                        row.push(Cow::Borrowed(chromosome));
+                        row.push(Cow::Owned(genomic.to_string()));
+                        row.push(Cow::Borrowed(base));
+                        row.push(Cow::Borrowed(sense));
+                    }
+                    _ => row.extend((0..COORDINATE_COLUMNS.len()).map(|_| Cow::Borrowed(""))),
+                }
+            }
+            if self.transcript_columns {
+                let located = structure.and_then(|s| s.locate(position));
+                let offsets = structure.and_then(|s| s.codon_offsets(position));
+                row.push(match located {
+                    Some(TranscriptPosition::Exon { index, .. }) => {
+                        Cow::Owned((index + 1).to_string())
+                    }
+                    _ => Cow::Borrowed(""),
+                });
+                match offsets {
+                    Some((start, stop)) => {
+                        row.push(Cow::Owned(start.to_string()));
+                        row.push(Cow::Owned(stop.to_string()));
+                    }
+                    None => row.extend([Cow::Borrowed(""), Cow::Borrowed("")]),
+                }
+                row.push(match located {
+                    Some(TranscriptPosition::Exon { number, .. }) => {
+                        Cow::Owned(format!("Exon {}", number))
+                    }
+                    Some(TranscriptPosition::Intron { number }) => {
+                        Cow::Owned(format!("Intron {}", number))
+                    }
+                    None => Cow::Borrowed(""),
+                });
+            }
+        }
+        if let Some(regions) = self.regions.as_mut() {
+            regions.extend_row(position, &mut row);
+        }
+        row
+    }
+}
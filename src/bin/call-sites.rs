@@ -0,0 +1,71 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use clap::Parser;
+
+use sgd_annotator::load_genome;
+use sgd_annotator::pipeline::{annotate, CommonArgs};
+use sgd_annotator::sites::{
+    annotate_sites, call_sites, write_sites_bed, write_sites_table, Threshold,
+};
+
+/// Calls highly reactive sites and annotates them with the features they
+/// overlap.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Profile, raw (gzip-compressed) or annotated
+    #[arg(short, long)]
+    input: String,
+
+    /// Table of the sites with their features, transcript positions and
+    /// codons
+    #[arg(short, long)]
+    output: String,
+
+    /// Also write the sites as BED6
+    #[arg(long)]
+    bed: Option<String>,
+
+    /// Reactivity a position must exceed: a value, `zscore:K` or
+    /// `percentile:P`, computed over the whole profile
+    #[arg(long, default_value = "percentile:95")]
+    threshold: Threshold,
+
+    /// Largest number of positions between reactive positions of one site
+    #[arg(long, default_value_t = 1)]
+    max_gap: usize,
+
+    /// Shortest site reported
+    #[arg(long, default_value_t = 1)]
+    min_length: usize,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    args.common.setup().unwrap();
+
+    let (genome, profile) = load_genome(Path::new(&args.input));
+    let (translator, models) = args.common.feature_models(&genome, &profile).unwrap();
+    let probed = args.common.probe.apply(&genome, &profile);
+
+    let threshold = args.threshold.resolve(&probed);
+    let mut sites = call_sites(
+        &probed,
+        threshold,
+        args.max_gap,
+        args.min_length,
+        &translator,
+    );
+    annotate_sites(&mut sites, &annotate(&models).unwrap(), &genome);
+
+    let mut writer = BufWriter::new(File::create(&args.output).unwrap());
+    write_sites_table(&mut writer, &sites, &translator).unwrap();
+    if let Some(path) = &args.bed {
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        write_sites_bed(&mut writer, &sites, &translator).unwrap();
+    }
+}
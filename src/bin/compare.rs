@@ -0,0 +1,88 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use clap::Parser;
+
+use sgd_annotator::compare::{
+    align, annotate_hits, delta, find_hits, test_windows, write_deltas, write_hits, Correction,
+};
+use sgd_annotator::load_genome;
+use sgd_annotator::pipeline::{annotate, CommonArgs};
+use sgd_annotator::translator::Translator;
+
+/// Finds regions whose reactivity differs between two conditions.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    /// Profile of the first condition, raw (gzip-compressed) or annotated
+    #[arg(long)]
+    first: String,
+
+    /// Profile of the second condition, raw (gzip-compressed) or annotated
+    #[arg(long)]
+    second: String,
+
+    /// Regions of significant change
+    #[arg(short, long)]
+    output: String,
+
+    /// Write both profiles and their difference at every position
+    #[arg(long)]
+    deltas: Option<String>,
+
+    /// Size of the sliding windows
+    #[arg(long, default_value_t = 50)]
+    window: usize,
+
+    /// Distance between the starts of consecutive windows
+    #[arg(long, default_value_t = 10)]
+    step: usize,
+
+    /// Minimal number of positions with a value in both profiles for a window
+    /// to be tested
+    #[arg(long, default_value_t = 10)]
+    min_values: usize,
+
+    /// Multiple-testing correction: `none`, `bonferroni` or `bh`
+    #[arg(long, default_value = "bh")]
+    correction: Correction,
+
+    /// Largest corrected p-value of a significant window
+    #[arg(long, default_value_t = 0.05)]
+    alpha: f32,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    args.common.setup().unwrap();
+
+    let (genome, first) = load_genome(Path::new(&args.first));
+    let (translator, models) = args.common.feature_models(&genome, &first).unwrap();
+    let (other_genome, second) = load_genome(Path::new(&args.second));
+    let second = align(&translator, &Translator::new(&other_genome), &second).unwrap();
+    let first = args.common.probe.apply(&genome, &first);
+    let second = args.common.probe.apply(&genome, &second);
+    let delta = delta(&first, &second);
+
+    if let Some(path) = &args.deltas {
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        write_deltas(&mut writer, &first, &second, &translator).unwrap();
+    }
+
+    let windows = test_windows(
+        &delta,
+        &translator,
+        args.window,
+        args.step,
+        args.min_values,
+        args.correction,
+    );
+    let mut hits = find_hits(&windows, &delta, args.alpha);
+    annotate_hits(&mut hits, &annotate(&models).unwrap());
+
+    let mut writer = BufWriter::new(File::create(&args.output).unwrap());
+    write_hits(&mut writer, &hits, &translator).unwrap();
+}
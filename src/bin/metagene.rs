@@ -0,0 +1,62 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use clap::Parser;
+
+use sgd_annotator::load_genome_gz;
+use sgd_annotator::metagene::{
+    landmark_curve, scaled_curves, write_curves, GeneNormalisation, Landmark,
+};
+use sgd_annotator::pipeline::CommonArgs;
+
+/// Aggregates the input profile around transcript landmarks.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    #[arg(short, long)]
+    input: String,
+
+    #[arg(short, long)]
+    output: String,
+
+    /// Landmarks to align on: `start`, `stop`, `donor`, `acceptor`, `tss`,
+    /// `pas`
+    #[arg(long, value_delimiter = ',', default_value = "start,stop")]
+    landmarks: Vec<Landmark>,
+
+    /// Nucleotides on either side of a landmark
+    #[arg(long, default_value_t = 50)]
+    flank: usize,
+
+    /// Also scale 5' UTRs, CDSs and 3' UTRs to this many bins each
+    #[arg(long)]
+    scaled_bins: Option<usize>,
+
+    /// Per-gene normalisation before aggregation: `none`, `mean` or `max`
+    #[arg(long, default_value = "none")]
+    normalisation: GeneNormalisation,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    args.common.setup().unwrap();
+
+    let (genome, profile) = load_genome_gz(Path::new(&args.input));
+    let (_, models) = args.common.feature_models(&genome, &profile).unwrap();
+    let probed = args.common.probe.apply(&genome, &profile);
+
+    let mut curves: Vec<_> = args
+        .landmarks
+        .iter()
+        .map(|landmark| landmark_curve(&models, *landmark, args.flank, args.normalisation, &probed))
+        .collect();
+    if let Some(bins) = args.scaled_bins {
+        curves.extend(scaled_curves(&models, bins, args.normalisation, &probed));
+    }
+
+    let mut writer = BufWriter::new(File::create(&args.output).unwrap());
+    write_curves(&mut writer, &curves).unwrap();
+}
@@ -0,0 +1,98 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use clap::Parser;
+use rayon::prelude::*;
+
+use sgd_annotator::load_genome_gz;
+use sgd_annotator::pipeline::CommonArgs;
+use sgd_annotator::structure::{
+    window_metrics, write_bedgraph, write_window_table, Metric, WindowMetrics, WindowOptions,
+};
+use sgd_annotator::transcript::TranscriptProfile;
+
+/// Computes sliding-window reactivity metrics along every transcript to tell
+/// structured from unstructured regions.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    #[arg(short, long)]
+    input: String,
+
+    /// Table of the metrics of every window
+    #[arg(short, long)]
+    output: String,
+
+    /// Also write one metric as a bedGraph track in genomic coordinates
+    #[arg(long)]
+    bedgraph: Option<String>,
+
+    /// Metric of the bedGraph track: `gini`, `entropy`, `mean` or `high`
+    #[arg(long, default_value = "gini")]
+    bedgraph_metric: Metric,
+
+    /// Size of the sliding windows, in transcript positions
+    #[arg(long, default_value_t = 50)]
+    window: usize,
+
+    /// Distance between the starts of consecutive windows
+    #[arg(long, default_value_t = 10)]
+    step: usize,
+
+    /// Minimal number of positions with a value for a window to get metrics
+    /// (defaults to half the window)
+    #[arg(long)]
+    min_values: Option<usize>,
+
+    /// Reactivity above which a base counts as highly reactive
+    #[arg(long, default_value_t = 0.7)]
+    high_reactivity: f32,
+
+    /// Only include these genes, by systematic or standard name
+    #[arg(long, value_delimiter = ',')]
+    genes: Vec<String>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    args.common.setup().unwrap();
+
+    let options = WindowOptions {
+        size: args.window,
+        step: args.step,
+        min_values: args.min_values.unwrap_or(args.window / 2),
+        high_reactivity: args.high_reactivity,
+    };
+
+    let (genome, profile) = load_genome_gz(Path::new(&args.input));
+    let (translator, mut models) = args.common.feature_models(&genome, &profile).unwrap();
+    let probed = args.common.probe.apply(&genome, &profile);
+
+    models.sort_by(|a, b| (&a.chromosome, a.range).cmp(&(&b.chromosome, b.range)));
+    let transcripts: Vec<(TranscriptProfile, Vec<WindowMetrics>)> = models
+        .par_iter()
+        .map(|model| TranscriptProfile::new(model, &genome, &probed))
+        .filter(|transcript| transcript.is_selected(&args.genes))
+        .map(|transcript| {
+            let windows = window_metrics(&transcript.values, &options);
+            (transcript, windows)
+        })
+        .collect();
+
+    let mut writer = BufWriter::new(File::create(&args.output).unwrap());
+    write_window_table(&mut writer, &transcripts).unwrap();
+    if let Some(path) = &args.bedgraph {
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        write_bedgraph(
+            &mut writer,
+            &transcripts,
+            args.bedgraph_metric,
+            &options,
+            &translator,
+        )
+        .unwrap();
+    }
+}
@@ -0,0 +1,76 @@
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader, BufWriter},
+    path::Path,
+};
+
+use clap::Parser;
+use rayon::prelude::*;
+
+use sgd_annotator::load_genome_gz;
+use sgd_annotator::pipeline::CommonArgs;
+use sgd_annotator::transcript::{ProfileFormat, TranscriptProfile};
+
+/// Writes the spliced sequence and profile of every transcript for structure
+/// modelling tools.
+#[derive(Parser)]
+#[command(version)]
+struct Args {
+    #[arg(short, long)]
+    input: String,
+
+    /// Directory receiving one file per transcript and format
+    #[arg(short, long)]
+    output: String,
+
+    /// Formats to write: `shape`, `map`, `xml`, `fasta`
+    #[arg(long, value_delimiter = ',', default_value = "shape,fasta")]
+    formats: Vec<ProfileFormat>,
+
+    /// Only export these genes, by systematic or standard name
+    #[arg(long, value_delimiter = ',')]
+    genes: Vec<String>,
+
+    /// File listing genes to export, one per line
+    #[arg(long)]
+    gene_list: Option<String>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+}
+
+fn main() {
+    let args = Args::parse();
+
+    args.common.setup().unwrap();
+
+    let mut names = args.genes.clone();
+    if let Some(path) = &args.gene_list {
+        let reader = BufReader::new(File::open(path).unwrap());
+        names.extend(
+            reader
+                .lines()
+                .map_while(Result::ok)
+                .map(|line| line.trim().to_string())
+                .filter(|line| !line.is_empty()),
+        );
+    }
+
+    let (genome, profile) = load_genome_gz(Path::new(&args.input));
+    let (_, models) = args.common.feature_models(&genome, &profile).unwrap();
+    let probed = args.common.probe.apply(&genome, &profile);
+
+    let output = Path::new(&args.output);
+    fs::create_dir_all(output).unwrap();
+    models.par_iter().for_each(|model| {
+        let transcript = TranscriptProfile::new(model, &genome, &probed);
+        if !transcript.is_selected(&names) {
+            return;
+        }
+        for format in args.formats.iter() {
+            let path = output.join(format!("{}.{}", transcript.file_stem(), format.extension()));
+            let mut writer = BufWriter::new(File::create(path).unwrap());
+            format.write(&mut writer, &transcript).unwrap();
+        }
+    });
+}
@@ -0,0 +1,91 @@
+use std::str::FromStr;
+
+use clap::Args;
+
+/// Probing chemistry of the experiment, deciding which bases carry
+/// meaningful reactivities.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Chemistry {
+    /// SHAPE reagents acylate all four bases.
+    Shape,
+    /// DMS methylates A and C.
+    Dms,
+    /// A custom set of reactive bases, e.g. `ACG`.
+    Custom(String),
+}
+
+impl FromStr for Chemistry {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shape" => Ok(Self::Shape),
+            "dms" => Ok(Self::Dms),
+            _ => {
+                let bases = s.to_ascii_uppercase().replace('T', "U");
+                if bases.is_empty() || !bases.chars().all(|c| "ACGU".contains(c)) {
+                    return Err(format!("Invalid chemistry: {}", s));
+                }
+                Ok(Self::Custom(bases))
+            }
+        }
+    }
+}
+
+impl Chemistry {
+    /// Bases whose reactivities are meaningful, as written in the input.
+    pub fn reactive_bases(&self) -> &str {
+        match self {
+            Self::Shape => "ACGU",
+            Self::Dms => "AC",
+            Self::Custom(bases) => bases,
+        }
+    }
+
+    pub fn is_reactive(&self, base: u8) -> bool {
+        self.reactive_bases().as_bytes().contains(&base)
+    }
+
+    /// Whether each position of `sequence` is a reactive base.
+    pub fn reactive(&self, sequence: &str) -> Vec<bool> {
+        sequence
+            .bytes()
+            .map(|base| self.is_reactive(base))
+            .collect()
+    }
+
+    /// Sets the values of unreactive bases of `sequence` to NaN.
+    pub fn mask(&self, sequence: &str, profile: &mut [f32]) {
+        for (value, base) in profile.iter_mut().zip(sequence.bytes()) {
+            if !self.is_reactive(base) {
+                *value = f32::NAN;
+            }
+        }
+    }
+}
+
+/// Command line options describing the probing experiment.
+#[derive(Args, Debug, Clone)]
+pub struct ProbeArgs {
+    /// Probing chemistry: `shape` (all bases), `dms` (A and C) or the
+    /// reactive bases, e.g. `ACG`
+    #[arg(long, default_value = "shape")]
+    pub chemistry: Chemistry,
+
+    /// Keep the values of unreactive bases and only flag them instead of
+    /// masking them
+    #[arg(long)]
+    pub flag_unreactive: bool,
+}
+
+impl ProbeArgs {
+    /// The profile used for statistics and exports: unreactive bases are
+    /// masked unless they are only flagged.
+    pub fn apply(&self, sequence: &str, profile: &[f32]) -> Vec<f32> {
+        let mut profile = profile.to_vec();
+        if !self.flag_unreactive {
+            self.chemistry.mask(sequence, &mut profile);
+        }
+        profile
+    }
+}
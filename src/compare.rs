@@ -0,0 +1,383 @@
+use std::{io::Write, str::FromStr};
+
+use anyhow::{bail, Context, Result};
+use rayon::prelude::*;
+
+use crate::{
+    annotation::{Annotations, Subtype},
+    stats::{mean, number},
+    translator::Translator,
+};
+
+/// Values of `profile`, indexed by `translator`, moved into the index space of
+/// `reference`. Both must hold the same chromosomes with the same lengths.
+pub fn align(reference: &Translator, translator: &Translator, profile: &[f32]) -> Result<Vec<f32>> {
+    let length = reference
+        .chromosomes()
+        .iter()
+        .map(|(_, offset, length)| offset + length)
+        .max()
+        .unwrap_or(0);
+    let mut aligned = vec![f32::NAN; length];
+    for (chromosome, offset, length) in reference.chromosomes() {
+        let other = translator.chromosome_offset(&chromosome).with_context(|| {
+            format!(
+                "Chromosome {} is missing from the second profile",
+                chromosome
+            )
+        })?;
+        if translator.chromosome_length(&chromosome) != Some(length) {
+            bail!(
+                "Chromosome {} differs in length between the profiles",
+                chromosome
+            );
+        }
+        for i in 0..length {
+            aligned[offset + i] = profile.get(other + i).copied().unwrap_or(f32::NAN);
+        }
+    }
+    Ok(aligned)
+}
+
+/// Per-position difference `second - first`.
+pub fn delta(first: &[f32], second: &[f32]) -> Vec<f32> {
+    first.iter().zip(second).map(|(a, b)| b - a).collect()
+}
+
+/// Upper tail probability of the standard normal distribution.
+fn normal_sf(z: f64) -> f64 {
+    // Complementary error function with a fractional error below 1.2e-7
+    // (Numerical Recipes, `erfcc`).
+    let x = z.abs() / std::f64::consts::SQRT_2;
+    let t = 1.0 / (1.0 + 0.5 * x);
+    let erfc = t
+        * (-x * x - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if z >= 0.0 {
+        erfc / 2.0
+    } else {
+        1.0 - erfc / 2.0
+    }
+}
+
+/// Two-sided p-value of the Wilcoxon signed-rank test that paired
+/// `differences` are centred on zero, using the normal approximation with a
+/// tie correction. Zero differences are dropped.
+pub fn wilcoxon_signed_rank(differences: &[f32]) -> f32 {
+    let mut nonzero: Vec<f64> = differences
+        .iter()
+        .filter(|d| !d.is_nan() && **d != 0.0)
+        .map(|d| *d as f64)
+        .collect();
+    let n = nonzero.len();
+    if n == 0 {
+        return f32::NAN;
+    }
+    nonzero.sort_by(|a, b| a.abs().total_cmp(&b.abs()));
+
+    let (mut w_plus, mut ties) = (0.0, 0.0);
+    let mut i = 0;
+    while i < n {
+        let mut j = i;
+        while j + 1 < n && nonzero[j + 1].abs() == nonzero[i].abs() {
+            j += 1;
+        }
+        let rank = (i + j) as f64 / 2.0 + 1.0;
+        w_plus += rank * nonzero[i..=j].iter().filter(|d| **d > 0.0).count() as f64;
+        let t = (j - i + 1) as f64;
+        ties += t * t * t - t;
+        i = j + 1;
+    }
+
+    let n = n as f64;
+    let mean = n * (n + 1.0) / 4.0;
+    let variance = n * (n + 1.0) * (2.0 * n + 1.0) / 24.0 - ties / 48.0;
+    if variance <= 0.0 {
+        return 1.0;
+    }
+    let z = (w_plus - mean).abs() / variance.sqrt();
+    (2.0 * normal_sf(z)).min(1.0) as f32
+}
+
+/// Multiple-testing correction applied to window p-values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Correction {
+    None,
+    Bonferroni,
+    /// Benjamini–Hochberg false discovery rate.
+    BenjaminiHochberg,
+}
+
+impl FromStr for Correction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "bonferroni" => Ok(Self::Bonferroni),
+            "bh" => Ok(Self::BenjaminiHochberg),
+            _ => Err(format!("Invalid correction: {}", s)),
+        }
+    }
+}
+
+impl Correction {
+    /// Adjusted `p_values`; NaN values are not counted as tests.
+    pub fn adjust(&self, p_values: &[f32]) -> Vec<f32> {
+        let m = p_values.iter().filter(|p| !p.is_nan()).count() as f32;
+        match self {
+            Self::None => p_values.to_vec(),
+            // `f32::min` would turn NaN into 1.
+            Self::Bonferroni => p_values
+                .iter()
+                .map(|p| {
+                    if p.is_nan() {
+                        f32::NAN
+                    } else {
+                        (p * m).min(1.0)
+                    }
+                })
+                .collect(),
+            Self::BenjaminiHochberg => {
+                let mut order: Vec<usize> = (0..p_values.len())
+                    .filter(|i| !p_values[*i].is_nan())
+                    .collect();
+                order.sort_by(|a, b| p_values[*b].total_cmp(&p_values[*a]));
+                let mut adjusted = vec![f32::NAN; p_values.len()];
+                let mut smallest = 1.0f32;
+                for (k, i) in order.iter().enumerate() {
+                    let rank = m - k as f32;
+                    smallest = smallest.min(p_values[*i] * m / rank);
+                    adjusted[*i] = smallest;
+                }
+                adjusted
+            }
+        }
+    }
+}
+
+/// A sliding window of the comparison. Ranges are input indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Window {
+    pub start: usize,
+    pub end: usize,
+    /// Positions with a value in both profiles.
+    pub values: usize,
+    pub mean_delta: f32,
+    pub p_value: f32,
+    pub q_value: f32,
+}
+
+/// Tests windows of `size` positions every `step` positions within each
+/// chromosome. Windows with fewer than `min_values` paired values are not
+/// tested.
+pub fn test_windows(
+    delta: &[f32],
+    translator: &Translator,
+    size: usize,
+    step: usize,
+    min_values: usize,
+    correction: Correction,
+) -> Vec<Window> {
+    let mut ranges = Vec::new();
+    for (_, offset, length) in translator.chromosomes() {
+        let mut start = 0;
+        while start + size <= length {
+            ranges.push((offset + start, offset + start + size - 1));
+            start += step.max(1);
+        }
+    }
+
+    let mut windows: Vec<Window> = ranges
+        .par_iter()
+        .filter_map(|(start, end)| {
+            let values: Vec<f32> = (*start..=*end)
+                .filter_map(|i| delta.get(i).copied())
+                .filter(|d| !d.is_nan())
+                .collect();
+            if values.len() < min_values.max(1) {
+                return None;
+            }
+            Some(Window {
+                start: *start,
+                end: *end,
+                values: values.len(),
+                mean_delta: mean(&values),
+                p_value: wilcoxon_signed_rank(&values),
+                q_value: f32::NAN,
+            })
+        })
+        .collect();
+
+    let p_values: Vec<f32> = windows.iter().map(|w| w.p_value).collect();
+    for (window, q) in windows.iter_mut().zip(correction.adjust(&p_values)) {
+        window.q_value = q;
+    }
+    windows
+}
+
+/// A feature overlapping a hit, with all subtypes it has there.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HitFeature {
+    pub systematic_name: String,
+    pub standard_name: String,
+    pub subtypes: Vec<Subtype>,
+}
+
+/// A region of significant change: overlapping significant windows changing
+/// in the same direction.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub start: usize,
+    pub end: usize,
+    pub windows: usize,
+    /// Mean difference over all paired positions of the region.
+    pub mean_delta: f32,
+    pub min_q_value: f32,
+    pub features: Vec<HitFeature>,
+}
+
+/// Merges the windows with a q-value of at most `alpha` into hits.
+pub fn find_hits(windows: &[Window], delta: &[f32], alpha: f32) -> Vec<Hit> {
+    let mut significant: Vec<&Window> = windows.iter().filter(|w| w.q_value <= alpha).collect();
+    significant.sort_by_key(|w| w.start);
+
+    let mut hits: Vec<Hit> = Vec::new();
+    for window in significant {
+        match hits.last_mut() {
+            Some(hit)
+                if window.start <= hit.end
+                    && (hit.mean_delta > 0.0) == (window.mean_delta > 0.0) =>
+            {
+                hit.end = hit.end.max(window.end);
+                hit.windows += 1;
+                hit.min_q_value = hit.min_q_value.min(window.q_value);
+            }
+            _ => hits.push(Hit {
+                start: window.start,
+                end: window.end,
+                windows: 1,
+                mean_delta: window.mean_delta,
+                min_q_value: window.q_value,
+                features: Vec::new(),
+            }),
+        }
+    }
+
+    for hit in hits.iter_mut() {
+        let values: Vec<f32> = (hit.start..=hit.end)
+            .filter_map(|i| delta.get(i).copied())
+            .filter(|d| !d.is_nan())
+            .collect();
+        hit.mean_delta = mean(&values);
+    }
+    hits
+}
+
+/// Fills in the features and subtypes overlapping each of `hits`, which must
+/// be sorted and must not overlap.
+pub fn annotate_hits(hits: &mut [Hit], annotations: &Annotations) {
+    let mut cursor = annotations.cursor();
+    for hit in hits.iter_mut() {
+        let mut features: Vec<HitFeature> = Vec::new();
+        for position in hit.start..=hit.end {
+            for (label, _, subtypes) in cursor.features(position) {
+                let feature = match features
+                    .iter_mut()
+                    .find(|f| f.systematic_name == label.systematic_name)
+                {
+                    Some(feature) => feature,
+                    None => {
+                        features.push(HitFeature {
+                            systematic_name: label.systematic_name.clone(),
+                            standard_name: label.standard_name.clone(),
+                            subtypes: Vec::new(),
+                        });
+                        features.last_mut().unwrap()
+                    }
+                };
+                for subtype in subtypes {
+                    if subtype != Subtype::Unknown && !feature.subtypes.contains(&subtype) {
+                        feature.subtypes.push(subtype);
+                    }
+                }
+            }
+        }
+        hit.features = features;
+    }
+}
+
+pub fn write_hits<W: Write>(writer: &mut W, hits: &[Hit], translator: &Translator) -> Result<()> {
+    writeln!(
+        writer,
+        "Chromosome\tStart\tEnd\tWindows\tMean delta\tMin q-value\tFeatures\tSubtypes"
+    )?;
+    for hit in hits {
+        let (chromosome, start) = translator
+            .locate(hit.start)
+            .with_context(|| format!("Position {} is not mapped", hit.start))?;
+        let end = start + hit.end - hit.start;
+        let names: Vec<&str> = hit
+            .features
+            .iter()
+            .map(|f| f.systematic_name.as_str())
+            .collect();
+        let subtypes: Vec<String> = hit
+            .features
+            .iter()
+            .map(|f| {
+                f.subtypes
+                    .iter()
+                    .map(|s| s.as_str())
+                    .collect::<Vec<_>>()
+                    .join(";")
+            })
+            .collect();
+        writeln!(
+            writer,
+            "chr{}\t{}\t{}\t{}\t{:.4}\t{:.4e}\t{}\t{}",
+            chromosome,
+            start,
+            end,
+            hit.windows,
+            hit.mean_delta,
+            hit.min_q_value,
+            names.join(","),
+            subtypes.join(",")
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes both profiles and their difference at every position.
+pub fn write_deltas<W: Write>(
+    writer: &mut W,
+    first: &[f32],
+    second: &[f32],
+    translator: &Translator,
+) -> Result<()> {
+    writeln!(writer, "Chromosome\tPosition\tFirst\tSecond\tDelta")?;
+    for (chromosome, offset, length) in translator.chromosomes() {
+        for i in 0..length {
+            let a = first.get(offset + i).copied().unwrap_or(f32::NAN);
+            let b = second.get(offset + i).copied().unwrap_or(f32::NAN);
+            writeln!(
+                writer,
+                "chr{}\t{}\t{}\t{}\t{}",
+                chromosome,
+                i + 1,
+                number(a),
+                number(b),
+                number(b - a)
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -117,6 +117,7 @@ pub fn ensure_all_data() -> Result<()> {
         ("rna_genomic.fasta.gz", "http://sgd-archive.yeastgenome.org/sequence/S288C_reference/rna/rna_genomic.fasta.gz"),
         ("rna_coding.fasta.gz", "http://sgd-archive.yeastgenome.org/sequence/S288C_reference/rna/rna_coding.fasta.gz"),
         ("other_features_genomic.fasta.gz", "http://sgd-archive.yeastgenome.org/sequence/S288C_reference/other_features/other_features_genomic.fasta.gz"),
+        ("saccharomyces_cerevisiae.gff.gz", "http://sgd-archive.yeastgenome.org/curation/chromosomal_feature/saccharomyces_cerevisiae.gff.gz"),
     ];
     for (filename, url) in &files {
         ensure_file(url, filename)?;
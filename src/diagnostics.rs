@@ -0,0 +1,84 @@
+use std::{fmt::Display, io::Write};
+
+use anyhow::Result;
+
+/// Why a feature, or part of it, was left out of the annotation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reason {
+    /// The feature failed validation, e.g. exons outside of the gene or a UTR
+    /// on the wrong side of the CDS.
+    Invalid,
+    /// An isoform was inconsistent with the gene and dropped; the gene kept
+    /// its other transcripts.
+    InvalidIsoform,
+    /// Another feature with the same systematic name was loaded first.
+    Duplicate,
+    /// The feature has no `ID` to name it by.
+    NoId,
+    /// The feature's chromosome was not found in the input.
+    Unmapped,
+    /// The feature extends past the end of its chromosome.
+    OffEdge,
+    /// A UTR extends past an end of the chromosome and was dropped.
+    UtrOffEdge,
+}
+
+impl Reason {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Invalid => "invalid",
+            Self::InvalidIsoform => "invalid-isoform",
+            Self::Duplicate => "duplicate",
+            Self::NoId => "no-id",
+            Self::Unmapped => "unmapped",
+            Self::OffEdge => "off-edge",
+            Self::UtrOffEdge => "utr-off-edge",
+        }
+    }
+}
+
+impl Display for Reason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// A feature that was skipped or altered while building feature models.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub systematic_name: String,
+    pub isoform: Option<String>,
+    pub reason: Reason,
+    pub detail: String,
+}
+
+impl Diagnostic {
+    pub fn new(systematic_name: &str, reason: Reason, detail: impl Into<String>) -> Self {
+        Self {
+            systematic_name: systematic_name.to_string(),
+            isoform: None,
+            reason,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn with_isoform(mut self, isoform: Option<&str>) -> Self {
+        self.isoform = isoform.map(|i| i.to_string());
+        self
+    }
+}
+
+pub fn write_diagnostics<W: Write>(writer: &mut W, diagnostics: &[Diagnostic]) -> Result<()> {
+    writeln!(writer, "Systematic name\tIsoform\tReason\tDetail")?;
+    for d in diagnostics {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}",
+            d.systematic_name,
+            d.isoform.as_deref().unwrap_or(""),
+            d.reason,
+            d.detail
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    gff::escape,
+    model::FeatureModel,
+    translator::{Strand, Translator},
+};
+
+/// Coordinate system used when exporting feature models.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CoordinateSpace {
+    /// Chromosome name and position in the reference genome.
+    Genome,
+    /// A single `input` sequence indexed by line number of the input profile.
+    Input,
+}
+
+impl FromStr for CoordinateSpace {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "genome" => Ok(Self::Genome),
+            "input" => Ok(Self::Input),
+            _ => Err(format!("Invalid coordinate space: {}", s)),
+        }
+    }
+}
+
+/// Converts input indices of a single model into 1-based positions on a named
+/// sequence.
+struct Locator {
+    seqid: String,
+    shift: usize,
+}
+
+impl Locator {
+    fn new(model: &FeatureModel, translator: &Translator, space: CoordinateSpace) -> Result<Self> {
+        match space {
+            CoordinateSpace::Genome => {
+                let shift = translator
+                    .chromosome_offset(&model.chromosome)
+                    .with_context(|| format!("Chromosome {} is not mapped", model.chromosome))?;
+                Ok(Self {
+                    seqid: format!("chr{}", model.chromosome),
+                    shift,
+                })
+            }
+            CoordinateSpace::Input => Ok(Self {
+                seqid: "input".to_string(),
+                shift: 0,
+            }),
+        }
+    }
+
+    fn position(&self, index: usize) -> usize {
+        index - self.shift + 1
+    }
+}
+
+/// Writes one BED12 line per feature. Thick parts mark the CDS of ORFs.
+pub fn write_bed12<W: Write>(
+    writer: &mut W,
+    models: &[FeatureModel],
+    translator: &Translator,
+    space: CoordinateSpace,
+) -> Result<()> {
+    for model in models {
+        let locator = Locator::new(model, translator, space)?;
+        let start = locator.position(model.range.0) - 1;
+        let end = locator.position(model.range.1);
+        let (thick_start, thick_end) = match model.cds_range() {
+            Some((s, e)) => (locator.position(s) - 1, locator.position(e)),
+            None => (start, start),
+        };
+        let blocks = model.blocks();
+        let sizes: String = blocks
+            .iter()
+            .map(|(s, e)| format!("{},", e - s + 1))
+            .collect();
+        let starts: String = blocks
+            .iter()
+            .map(|(s, _)| format!("{},", locator.position(*s) - 1 - start))
+            .collect();
+
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t0\t{}\t{}\t{}\t0\t{}\t{}\t{}",
+            locator.seqid,
+            start,
+            end,
+            model.id(),
+            model.strand,
+            thick_start,
+            thick_end,
+            blocks.len(),
+            sizes,
+            starts
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes a gene → mRNA → exon/CDS/UTR hierarchy for ORFs and a gene →
+/// transcript → exon one for the remaining features.
+pub fn write_gff3<W: Write>(
+    writer: &mut W,
+    models: &[FeatureModel],
+    translator: &Translator,
+    space: CoordinateSpace,
+) -> Result<()> {
+    // Isoforms share one gene record spanning all of them.
+    let mut genes: HashMap<&str, (usize, usize)> = HashMap::new();
+    for model in models {
+        let span = genes.entry(&model.systematic_name).or_insert(model.range);
+        *span = (span.0.min(model.range.0), span.1.max(model.range.1));
+    }
+    let mut written = HashSet::new();
+
+    writeln!(writer, "##gff-version 3")?;
+    for model in models {
+        let locator = Locator::new(model, translator, space)?;
+        let line =
+            |writer: &mut W, kind: &str, range: (usize, usize), phase: &str, attributes: String| {
+                writeln!(
+                    writer,
+                    "{}\tsgd-annotator\t{}\t{}\t{}\t.\t{}\t{}\t{}",
+                    locator.seqid,
+                    kind,
+                    locator.position(range.0),
+                    locator.position(range.1),
+                    model.strand,
+                    phase,
+                    attributes
+                )
+            };
+
+        let gene_id = escape(&model.systematic_name);
+        let transcript_id = escape(&model.transcript_id());
+        let transcript_kind = if model.is_coding() {
+            "mRNA"
+        } else {
+            "transcript"
+        };
+
+        let mut attributes = format!(
+            "ID={};Name={};gene={};Note={}",
+            gene_id,
+            gene_id,
+            escape(&model.standard_name),
+            escape(&model.category)
+        );
+        if let Some(qualifier) = &model.qualifier {
+            attributes += &format!(";orf_classification={}", escape(qualifier));
+        }
+        if written.insert(&model.systematic_name) {
+            let span = genes[model.systematic_name.as_str()];
+            line(writer, "gene", span, ".", attributes)?;
+        }
+        line(
+            writer,
+            transcript_kind,
+            model.range,
+            ".",
+            format!("ID={};Parent={}", transcript_id, gene_id),
+        )?;
+        for (i, block) in model.blocks().iter().enumerate() {
+            line(
+                writer,
+                "exon",
+                *block,
+                ".",
+                format!(
+                    "ID={}_exon{};Parent={}",
+                    transcript_id,
+                    i + 1,
+                    transcript_id
+                ),
+            )?;
+        }
+        if model.is_coding() {
+            for (range, phase) in cds_phases(model) {
+                line(
+                    writer,
+                    "CDS",
+                    range,
+                    &phase.to_string(),
+                    format!("ID={}_CDS;Parent={}", transcript_id, transcript_id),
+                )?;
+            }
+        }
+        if let Some(utr) = model.utr5 {
+            line(
+                writer,
+                "five_prime_UTR",
+                utr,
+                ".",
+                format!("Parent={}", transcript_id),
+            )?;
+        }
+        if let Some(utr) = model.utr3 {
+            line(
+                writer,
+                "three_prime_UTR",
+                utr,
+                ".",
+                format!("Parent={}", transcript_id),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// A GTF attribute value with quotes and backslashes escaped.
+fn gtf_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Writes transcript, exon, CDS and UTR records with `gene_id` and
+/// `transcript_id` attributes.
+pub fn write_gtf<W: Write>(
+    writer: &mut W,
+    models: &[FeatureModel],
+    translator: &Translator,
+    space: CoordinateSpace,
+) -> Result<()> {
+    for model in models {
+        let locator = Locator::new(model, translator, space)?;
+        let attributes = format!(
+            "gene_id \"{}\"; transcript_id \"{}\"; gene_name \"{}\"; gene_type \"{}\";",
+            gtf_value(&model.systematic_name),
+            gtf_value(&model.transcript_id()),
+            gtf_value(&model.standard_name),
+            gtf_value(&model.category)
+        );
+        let line = |writer: &mut W, kind: &str, range: (usize, usize), phase: &str| {
+            writeln!(
+                writer,
+                "{}\tsgd-annotator\t{}\t{}\t{}\t.\t{}\t{}\t{}",
+                locator.seqid,
+                kind,
+                locator.position(range.0),
+                locator.position(range.1),
+                model.strand,
+                phase,
+                attributes
+            )
+        };
+
+        line(writer, "transcript", model.range, ".")?;
+        for block in model.blocks() {
+            line(writer, "exon", block, ".")?;
+        }
+        if model.is_coding() {
+            for (range, phase) in cds_phases(model) {
+                line(writer, "CDS", range, &phase.to_string())?;
+            }
+        }
+        if let Some(utr) = model.utr5 {
+            line(writer, "five_prime_utr", utr, ".")?;
+        }
+        if let Some(utr) = model.utr3 {
+            line(writer, "three_prime_utr", utr, ".")?;
+        }
+    }
+    Ok(())
+}
+
+/// Pairs every CDS piece with its phase, counted in the direction of
+/// transcription.
+fn cds_phases(model: &FeatureModel) -> Vec<((usize, usize), usize)> {
+    let mut exons = model.exons.clone();
+    if model.strand == Strand::Minus {
+        exons.reverse();
+    }
+
+    let mut length = 0;
+    let mut result = Vec::with_capacity(exons.len());
+    for (start, end) in exons {
+        result.push(((start, end), (3 - length % 3) % 3));
+        length += end - start + 1;
+    }
+    if model.strand == Strand::Minus {
+        result.reverse();
+    }
+    result
+}
@@ -9,7 +9,7 @@ use std::{
 use flate2::read::GzDecoder;
 use regex::Regex;
 
-use crate::translator::{GenomicRange, YeastChromosome};
+use crate::translator::{GenomicRange, Strand, YeastChromosome};
 
 #[derive(Debug)]
 pub struct Fasta {
@@ -88,6 +88,19 @@ impl Fasta {
         FastaType::Gene
     }
 
+    pub fn strand(&self) -> Strand {
+        let is_minus = match self.fasta_type() {
+            FastaType::Chromosome => false,
+            FastaType::Gene => self.header.contains("reverse complement"),
+            FastaType::UTR => self.header.contains("strand=-"),
+        };
+        if is_minus {
+            Strand::Minus
+        } else {
+            Strand::Plus
+        }
+    }
+
     pub fn genomic_range(&self) -> GenomicRange {
         match self.fasta_type() {
             FastaType::Chromosome => self.genomic_range_for_chromosome(),
@@ -229,6 +242,22 @@ impl Fasta {
         None
     }
 
+    /// SGD feature type from the header, e.g. `Verified ORF` or `tRNA_gene`.
+    pub fn feature_type(&self) -> Option<String> {
+        match self.fasta_type() {
+            FastaType::Chromosome => None,
+            FastaType::Gene => self.feature_type_for_gene(),
+            FastaType::UTR => None,
+        }
+    }
+
+    fn feature_type_for_gene(&self) -> Option<String> {
+        let regex =
+            Regex::new(r#"Genome Release [^,]+, (?:reverse complement, )?([^,"]+)"#).unwrap();
+        let captures = regex.captures(&self.header)?;
+        Some(captures[1].trim().to_string())
+    }
+
     pub fn systematic_name(&self) -> String {
         match self.fasta_type() {
             FastaType::Chromosome => self.systematic_name_for_chromosome(),
@@ -256,6 +285,14 @@ impl Fasta {
             .unwrap()
     }
 
+    /// Identifier distinguishing several UTRs of one gene, e.g. `id001`.
+    pub fn utr_id(&self) -> Option<String> {
+        match self.fasta_type() {
+            FastaType::UTR => self.header.split('_').nth(5).map(|s| s.to_string()),
+            _ => None,
+        }
+    }
+
     pub fn standard_name(&self) -> String {
         match self.fasta_type() {
             FastaType::Chromosome => self.standard_name_for_chromosome(),
@@ -281,26 +318,20 @@ impl Fasta {
     }
 }
 
-pub fn load_fasta_gz(path: &Path) -> HashMap<String, Fasta> {
+/// Reads every record of a gzipped FASTA file, in file order.
+pub fn load_fasta_records_gz(path: &Path) -> Vec<Fasta> {
     let file = File::open(path).unwrap();
     let decoder = GzDecoder::new(file);
     let reader = BufReader::new(decoder);
     let mut header = String::new();
     let mut sequence = String::new();
-    let mut result = HashMap::new();
+    let mut result = Vec::new();
 
     for line in reader.lines() {
         if let Ok(content) = line {
             if content.starts_with(">") {
                 if !header.is_empty() {
-                    let fasta = Fasta::new(&header, &sequence);
-                    if !result.contains_key(&fasta.systematic_name()) {
-                        result.insert(fasta.systematic_name(), fasta);
-                    } else {
-                        if sequence.len() > result[&fasta.systematic_name()].sequence.len() {
-                            result.insert(fasta.systematic_name(), fasta);
-                        }
-                    }
+                    result.push(Fasta::new(&header, &sequence));
                     header.clear();
                     sequence.clear();
                 }
@@ -312,13 +343,24 @@ pub fn load_fasta_gz(path: &Path) -> HashMap<String, Fasta> {
     }
 
     if !header.is_empty() {
-        let fasta = Fasta::new(&header, &sequence);
-        if !result.contains_key(&fasta.systematic_name()) {
-            result.insert(fasta.systematic_name(), fasta);
-        } else {
-            if sequence.len() > result[&fasta.systematic_name()].sequence.len() {
-                result.insert(fasta.systematic_name(), fasta);
-            }
+        result.push(Fasta::new(&header, &sequence));
+    }
+
+    result
+}
+
+/// Reads a gzipped FASTA file keyed by systematic name. Of several records
+/// with the same name, the one with the longest sequence is kept.
+pub fn load_fasta_gz(path: &Path) -> HashMap<String, Fasta> {
+    let mut result: HashMap<String, Fasta> = HashMap::new();
+
+    for fasta in load_fasta_records_gz(path) {
+        let name = fasta.systematic_name();
+        let is_longer = result
+            .get(&name)
+            .is_none_or(|other| fasta.sequence.len() > other.sequence.len());
+        if is_longer {
+            result.insert(name, fasta);
         }
     }
 
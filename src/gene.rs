@@ -0,0 +1,601 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    diagnostics::{Diagnostic, Reason},
+    fasta::{load_fasta_gz, Fasta},
+    gff::{Gff, GffRecord},
+    isoform::{select_isoforms, Isoform, IsoformSelection, UtrCandidate, UtrCatalog},
+    model::feature_category,
+    stats::{median, sorted},
+    translator::{GenomicRange, Strand, YeastChromosome},
+};
+
+/// A coding range: a CDS piece of an ORF or an exon of a noncoding RNA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Exon {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Intron {
+    pub start: usize,
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Utr {
+    pub start: usize,
+    pub end: usize,
+    /// Dataset and record the UTR comes from, e.g. `SGD:id001`.
+    pub source: String,
+    /// Whether the UTR was guessed by a [`UtrFallback`] rather than observed.
+    pub inferred: bool,
+}
+
+/// One transcript of a gene. All positions are genomic and 1-based.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    /// Provenance of the UTRs, e.g. `SGD:id001`, when the transcript is one of
+    /// several isoforms.
+    pub isoform: Option<String>,
+    pub exons: Vec<Exon>,
+    pub introns: Vec<Intron>,
+    pub utr5: Option<Utr>,
+    pub utr3: Option<Utr>,
+}
+
+impl Transcript {
+    fn utrs(&self) -> impl Iterator<Item = &Utr> {
+        self.utr5.iter().chain(self.utr3.iter())
+    }
+}
+
+/// A gene as described by SGD, with its transcripts in genomic coordinates.
+#[derive(Debug, Clone)]
+pub struct Gene {
+    pub systematic_name: String,
+    pub standard_name: String,
+    /// SGD feature type in readable form, e.g. `ORF`, `tRNA`, `ARS`.
+    pub category: String,
+    /// ORF classification: `Verified`, `Uncharacterized` or `Dubious`.
+    pub qualifier: Option<String>,
+    /// Whether exons are CDS pieces rather than exons of a noncoding RNA.
+    pub coding: bool,
+    pub chromosome: YeastChromosome,
+    pub strand: Strand,
+    pub start: usize,
+    pub end: usize,
+    pub transcripts: Vec<Transcript>,
+}
+
+impl Gene {
+    pub fn genomic_range(&self) -> GenomicRange {
+        GenomicRange {
+            chromosome: self.chromosome.clone(),
+            start: self.start,
+            end: self.end,
+        }
+    }
+
+    /// Span from the first to the last coding nucleotide of an ORF.
+    pub fn cds_range(&self) -> Option<(usize, usize)> {
+        if !self.coding {
+            return None;
+        }
+        let exons = &self.transcripts.first()?.exons;
+        Some((exons.first()?.start, exons.last()?.end))
+    }
+
+    /// Checks that exons and introns lie inside the gene, that no intron
+    /// overlaps an exon and that every UTR lies on its side of the CDS.
+    pub fn validate(&self) -> Result<()> {
+        if self.start > self.end {
+            bail!(
+                "{}: start {} after end {}",
+                self.systematic_name,
+                self.start,
+                self.end
+            );
+        }
+        if self.transcripts.is_empty() {
+            bail!("{}: no transcript", self.systematic_name);
+        }
+        for transcript in &self.transcripts {
+            self.validate_transcript(transcript)?;
+        }
+        Ok(())
+    }
+
+    fn validate_transcript(&self, transcript: &Transcript) -> Result<()> {
+        let name = &self.systematic_name;
+        let pieces = transcript
+            .exons
+            .iter()
+            .map(|e| (e.start, e.end))
+            .chain(transcript.introns.iter().map(|i| (i.start, i.end)));
+        for (start, end) in pieces {
+            if start > end || start < self.start || end > self.end {
+                bail!("{}: {}-{} outside of the gene", name, start, end);
+            }
+        }
+        // Exons may touch or overlap at ribosomal frameshifts, introns may not.
+        for intron in &transcript.introns {
+            if let Some(exon) = transcript
+                .exons
+                .iter()
+                .find(|e| e.start <= intron.end && intron.start <= e.end)
+            {
+                bail!(
+                    "{}: intron {}-{} overlaps exon {}-{}",
+                    name,
+                    intron.start,
+                    intron.end,
+                    exon.start,
+                    exon.end
+                );
+            }
+        }
+
+        if let Some((cds_start, cds_end)) = self.cds_range() {
+            let upstream = |utr: &Utr| match self.strand {
+                Strand::Plus => utr.start < cds_start,
+                Strand::Minus => utr.end > cds_end,
+            };
+            let downstream = |utr: &Utr| match self.strand {
+                Strand::Plus => utr.end > cds_end,
+                Strand::Minus => utr.start < cds_start,
+            };
+            if transcript.utr5.as_ref().is_some_and(|u| !upstream(u)) {
+                bail!("{}: 5' UTR not upstream of the CDS", name);
+            }
+            if transcript.utr3.as_ref().is_some_and(|u| !downstream(u)) {
+                bail!("{}: 3' UTR not downstream of the CDS", name);
+            }
+        }
+        if transcript.utrs().any(|u| u.start > u.end) {
+            bail!("{}: empty UTR", name);
+        }
+        Ok(())
+    }
+
+    /// Replaces the transcripts by one per isoform of `catalog` chosen by
+    /// `selection`. UTRs already present (e.g. from a GFF3 file) count as one
+    /// more isoform. Isoforms inconsistent with the gene are dropped and
+    /// reported.
+    pub fn with_isoforms(
+        mut self,
+        catalog: &UtrCatalog,
+        selection: IsoformSelection,
+    ) -> (Self, Vec<Diagnostic>) {
+        let Some(base) = self.transcripts.first().cloned() else {
+            return (self, Vec::new());
+        };
+
+        let mut isoforms = catalog.isoforms(&self.systematic_name, &self.genomic_range());
+        if base.utr5.is_some() || base.utr3.is_some() {
+            let candidate = |utr: &Utr, is_5p: bool| {
+                let (source, id) = utr.source.split_once(':').unwrap_or((&utr.source, ""));
+                UtrCandidate {
+                    source: source.to_string(),
+                    id: id.to_string(),
+                    is_5p,
+                    range: GenomicRange {
+                        chromosome: self.chromosome.clone(),
+                        start: utr.start,
+                        end: utr.end,
+                    },
+                    strand: self.strand,
+                    support: None,
+                }
+            };
+            let utr5 = base.utr5.as_ref().map(|u| candidate(u, true));
+            let utr3 = base.utr3.as_ref().map(|u| candidate(u, false));
+            let label = utr5.as_ref().or(utr3.as_ref()).unwrap();
+            isoforms.push(Isoform {
+                source: label.source.clone(),
+                id: label.id.clone(),
+                utr5,
+                utr3,
+                support: None,
+            });
+        }
+        if isoforms.is_empty() {
+            return (self, Vec::new());
+        }
+
+        let utr = |candidate: &Option<UtrCandidate>| {
+            candidate.as_ref().map(|c| Utr {
+                start: c.range.start,
+                end: c.range.end,
+                source: format!("{}:{}", c.source, c.id),
+                inferred: false,
+            })
+        };
+        let mut diagnostics = Vec::new();
+        let transcripts: Vec<Transcript> = select_isoforms(isoforms, selection)
+            .iter()
+            .map(|isoform| Transcript {
+                isoform: Some(isoform.label()),
+                exons: base.exons.clone(),
+                introns: base.introns.clone(),
+                utr5: utr(&isoform.utr5),
+                utr3: utr(&isoform.utr3),
+            })
+            .filter(|transcript| match self.validate_transcript(transcript) {
+                Ok(()) => true,
+                Err(e) => {
+                    diagnostics.push(
+                        self.diagnostic(Reason::InvalidIsoform, &e)
+                            .with_isoform(transcript.isoform.as_deref()),
+                    );
+                    false
+                }
+            })
+            .collect();
+        if !transcripts.is_empty() {
+            self.transcripts = transcripts;
+        }
+        (self, diagnostics)
+    }
+
+    /// Reports `error`, raised by validating this gene, with `reason`.
+    fn diagnostic(&self, reason: Reason, error: &anyhow::Error) -> Diagnostic {
+        let message = error.to_string();
+        let prefix = format!("{}: ", self.systematic_name);
+        let detail = message.strip_prefix(&prefix).unwrap_or(&message);
+        Diagnostic::new(&self.systematic_name, reason, detail)
+    }
+
+    fn from_fasta(genomic: &Fasta, coding: Option<&Fasta>, fallback: &str, is_orf: bool) -> Self {
+        let range = genomic.genomic_range();
+        let (category, qualifier) =
+            feature_category(genomic.feature_type().as_deref().unwrap_or(fallback));
+        let exons = coding
+            .and_then(|c| c.coding_ranges())
+            .unwrap_or_default()
+            .iter()
+            .map(|r| Exon {
+                start: r.start,
+                end: r.end,
+            })
+            .collect();
+        let introns = coding
+            .and_then(|c| c.noncoding_ranges())
+            .unwrap_or_default()
+            .iter()
+            .map(|r| Intron {
+                start: r.start,
+                end: r.end,
+            })
+            .collect();
+
+        Self {
+            systematic_name: genomic.systematic_name(),
+            standard_name: genomic.standard_name(),
+            category,
+            qualifier,
+            coding: is_orf,
+            chromosome: range.chromosome,
+            strand: genomic.strand(),
+            start: range.start,
+            end: range.end,
+            transcripts: vec![Transcript {
+                isoform: None,
+                exons,
+                introns,
+                utr5: None,
+                utr3: None,
+            }],
+        }
+    }
+
+    /// Builds the gene of a top-level GFF3 feature. Features without an `ID`
+    /// or on a sequence that is not a yeast chromosome are reported instead.
+    fn from_gff(gff: &Gff, record: &GffRecord) -> Result<Self, Diagnostic> {
+        let name = record.id().ok_or_else(|| {
+            Diagnostic::new(
+                record.attribute("Name").unwrap_or_default(),
+                Reason::NoId,
+                format!(
+                    "{} at {}:{}-{}",
+                    record.kind, record.seqid, record.start, record.end
+                ),
+            )
+        })?;
+        let range = record.genomic_range().ok_or_else(|| {
+            Diagnostic::new(
+                name,
+                Reason::Unmapped,
+                format!("{} is not a yeast chromosome", record.seqid),
+            )
+        })?;
+        let descendants = gff.descendants(record);
+
+        let coding = descendants.iter().any(|r| r.kind == "CDS");
+        let (category, _) = feature_category(&record.kind);
+        let qualifier = record
+            .attribute("orf_classification")
+            .map(|s| s.to_string());
+
+        let mut exons: Vec<Exon> = descendants
+            .iter()
+            .filter(|r| {
+                if coding {
+                    r.kind == "CDS"
+                } else {
+                    r.kind == "exon" || r.kind == "noncoding_exon"
+                }
+            })
+            .map(|r| Exon {
+                start: r.start,
+                end: r.end,
+            })
+            .collect();
+        exons.sort();
+        exons.dedup();
+        // Prefer the annotated introns; otherwise take the gaps between exons,
+        // skipping pieces that touch or overlap at frameshifts.
+        let mut introns: Vec<Intron> = descendants
+            .iter()
+            .filter(|r| r.kind == "intron")
+            .map(|r| Intron {
+                start: r.start,
+                end: r.end,
+            })
+            .collect();
+        if introns.is_empty() {
+            introns = exons
+                .windows(2)
+                .filter(|pair| pair[1].start > pair[0].end + 1)
+                .map(|pair| Intron {
+                    start: pair[0].end + 1,
+                    end: pair[1].start - 1,
+                })
+                .collect();
+        }
+        introns.sort();
+        introns.dedup();
+
+        let utr = |kind: &str| {
+            let child = descendants.iter().find(|r| r.kind == kind)?;
+            Some(Utr {
+                start: child.start,
+                end: child.end,
+                source: format!("GFF:{}", name),
+                inferred: false,
+            })
+        };
+        let standard_name = record
+            .attribute("gene")
+            .or(record.attribute("Name"))
+            .unwrap_or(name);
+
+        Ok(Self {
+            systematic_name: name.to_string(),
+            standard_name: standard_name.to_string(),
+            category,
+            qualifier,
+            coding,
+            chromosome: range.chromosome,
+            strand: record.strand.unwrap_or(Strand::Plus),
+            start: range.start,
+            end: range.end,
+            transcripts: vec![Transcript {
+                isoform: None,
+                exons,
+                introns,
+                utr5: utr("five_prime_UTR"),
+                utr3: utr("three_prime_UTR"),
+            }],
+        })
+    }
+}
+
+/// Drops genes failing validation and all but the first of several genes
+/// sharing a systematic name, reporting each of them.
+fn validated(genes: impl Iterator<Item = Gene>) -> (Vec<Gene>, Vec<Diagnostic>) {
+    let mut seen = HashSet::new();
+    let mut diagnostics = Vec::new();
+    let genes = genes
+        .filter(|gene| {
+            if let Err(e) = gene.validate() {
+                diagnostics.push(gene.diagnostic(Reason::Invalid, &e));
+                return false;
+            }
+            if !seen.insert(gene.systematic_name.clone()) {
+                diagnostics.push(Diagnostic::new(
+                    &gene.systematic_name,
+                    Reason::Duplicate,
+                    format!("chr{}:{}-{}", gene.chromosome, gene.start, gene.end),
+                ));
+                return false;
+            }
+            true
+        })
+        .collect();
+    (genes, diagnostics)
+}
+
+/// Builds genes from the top-level features of a GFF3 file, together with
+/// the features and genes that were dropped.
+pub fn genes_from_gff(gff: &Gff) -> (Vec<Gene>, Vec<Diagnostic>) {
+    let mut skipped = Vec::new();
+    let genes: Vec<Gene> = gff
+        .roots()
+        .filter(|record| record.kind != "chromosome")
+        .filter_map(|record| {
+            Gene::from_gff(gff, record)
+                .map_err(|diagnostic| skipped.push(diagnostic))
+                .ok()
+        })
+        .collect();
+    let (genes, diagnostics) = validated(genes.into_iter());
+    skipped.extend(diagnostics);
+    (genes, skipped)
+}
+
+/// Builds genes from SGD's genomic and coding FASTA files of ORFs, RNAs and
+/// other features found in `directory`, together with the genes that were
+/// dropped.
+pub fn load_genes_from_fasta(directory: &Path) -> (Vec<Gene>, Vec<Diagnostic>) {
+    let load = |name: &str| load_fasta_gz(&directory.join(name));
+    let orf_genomic = load("orf_genomic.fasta.gz");
+    let rna_genomic = load("rna_genomic.fasta.gz");
+    let other_genomic = load("other_features_genomic.fasta.gz");
+    let orf_coding = load("orf_coding.fasta.gz");
+    let rna_coding = load("rna_coding.fasta.gz");
+
+    let no_coding = HashMap::new();
+    let sources = [
+        (&orf_genomic, &orf_coding, "ORF"),
+        (&rna_genomic, &rna_coding, "RNA"),
+        (&other_genomic, &no_coding, "Other"),
+    ];
+    validated(sources.iter().flat_map(|(genomic, coding, fallback)| {
+        genomic.iter().map(move |(name, fasta)| {
+            Gene::from_fasta(fasta, coding.get(name), fallback, *fallback == "ORF")
+        })
+    }))
+}
+
+/// How UTRs are guessed for ORFs that have none in any UTR dataset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UtrFallback {
+    /// Leave the UTR out.
+    None,
+    /// Extend the ORF by a fixed length.
+    Fixed,
+    /// Extend the ORF by the genome-wide median length of observed UTRs of
+    /// the same end.
+    Median,
+    /// Extend the ORF by a fixed length, but never into the neighbouring
+    /// feature.
+    Neighbour,
+}
+
+impl FromStr for UtrFallback {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "fixed" => Ok(Self::Fixed),
+            "median" => Ok(Self::Median),
+            "neighbour" => Ok(Self::Neighbour),
+            _ => Err(format!("Invalid UTR fallback: {}", s)),
+        }
+    }
+}
+
+/// Median of `lengths`, rounded to the nearest length.
+fn median_length(lengths: Vec<usize>) -> Option<usize> {
+    let lengths: Vec<f32> = lengths.into_iter().map(|x| x as f32).collect();
+    Some(median(&sorted(&lengths)))
+        .filter(|m| !m.is_nan())
+        .map(|m| m.round() as usize)
+}
+
+/// Gives every transcript of an ORF lacking a 5' or 3' UTR an inferred one
+/// according to `fallback`. `length` is the fixed or maximal extension and
+/// the median used when no UTR was observed at all. UTRs are clipped to the
+/// ends of the chromosome, as given by `chromosome_lengths`.
+pub fn infer_missing_utrs(
+    genes: &mut [Gene],
+    fallback: UtrFallback,
+    length: usize,
+    chromosome_lengths: &HashMap<YeastChromosome, usize>,
+) {
+    if fallback == UtrFallback::None {
+        return;
+    }
+
+    let observed = |is_5p: bool| -> Vec<usize> {
+        genes
+            .iter()
+            .flat_map(|g| g.transcripts.iter())
+            .filter_map(|t| {
+                if is_5p {
+                    t.utr5.as_ref()
+                } else {
+                    t.utr3.as_ref()
+                }
+            })
+            .filter(|u| !u.inferred)
+            .map(|u| u.end - u.start + 1)
+            .collect()
+    };
+    let (length5, length3) = match fallback {
+        UtrFallback::Median => (
+            median_length(observed(true)).unwrap_or(length),
+            median_length(observed(false)).unwrap_or(length),
+        ),
+        _ => (length, length),
+    };
+
+    // Sorted ends and starts of all features, per chromosome, to find the
+    // closest neighbour on either side.
+    let mut bounds: HashMap<YeastChromosome, (Vec<usize>, Vec<usize>)> = HashMap::new();
+    for gene in genes.iter() {
+        let (ends, starts) = bounds.entry(gene.chromosome.clone()).or_default();
+        ends.push(gene.end);
+        starts.push(gene.start);
+    }
+    for (ends, starts) in bounds.values_mut() {
+        ends.sort_unstable();
+        starts.sort_unstable();
+    }
+
+    for gene in genes.iter_mut() {
+        if gene.cds_range().is_none() {
+            continue;
+        }
+        let (ends, starts) = &bounds[&gene.chromosome];
+        let (mut left, mut right) = match gene.strand {
+            Strand::Plus => (length5, length3),
+            Strand::Minus => (length3, length5),
+        };
+        if fallback == UtrFallback::Neighbour {
+            let previous_end = ends[..ends.partition_point(|e| *e < gene.start)].last();
+            let next_start = starts.get(starts.partition_point(|s| *s <= gene.end));
+            left = left.min(previous_end.map_or(usize::MAX, |e| gene.start - e - 1));
+            right = right.min(next_start.map_or(usize::MAX, |s| s - gene.end - 1));
+        }
+        left = left.min(gene.start - 1);
+        if let Some(length) = chromosome_lengths.get(&gene.chromosome) {
+            right = right.min(length.saturating_sub(gene.end));
+        }
+
+        let source = match fallback {
+            UtrFallback::Median => "inferred:median",
+            UtrFallback::Neighbour => "inferred:neighbour",
+            _ => "inferred:fixed",
+        };
+        let utr = |start: usize, end: usize| Utr {
+            start,
+            end,
+            source: source.to_string(),
+            inferred: true,
+        };
+        let left_utr = (left > 0).then(|| utr(gene.start - left, gene.start - 1));
+        let right_utr = (right > 0).then(|| utr(gene.end + 1, gene.end + right));
+        let (utr5, utr3) = match gene.strand {
+            Strand::Plus => (left_utr, right_utr),
+            Strand::Minus => (right_utr, left_utr),
+        };
+        for transcript in gene.transcripts.iter_mut() {
+            if transcript.utr5.is_none() {
+                transcript.utr5 = utr5.clone();
+            }
+            if transcript.utr3.is_none() {
+                transcript.utr3 = utr3.clone();
+            }
+        }
+    }
+}
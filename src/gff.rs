@@ -0,0 +1,191 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+
+use crate::translator::{GenomicRange, Strand, YeastChromosome};
+
+#[derive(Debug, Clone)]
+pub struct GffRecord {
+    pub seqid: String,
+    pub source: String,
+    pub kind: String,
+    pub start: usize,
+    pub end: usize,
+    pub strand: Option<Strand>,
+    pub phase: Option<usize>,
+    pub attributes: HashMap<String, String>,
+}
+
+impl GffRecord {
+    fn parse(line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 9 {
+            bail!("Expected 9 columns, found {}", fields.len());
+        }
+
+        let strand = match fields[6] {
+            "+" => Some(Strand::Plus),
+            "-" => Some(Strand::Minus),
+            _ => None,
+        };
+        let attributes = fields[8]
+            .split(';')
+            .filter(|pair| !pair.is_empty())
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), unescape(value)))
+            .collect();
+
+        Ok(Self {
+            seqid: fields[0].to_string(),
+            source: fields[1].to_string(),
+            kind: fields[2].to_string(),
+            start: fields[3].parse()?,
+            end: fields[4].parse()?,
+            strand,
+            phase: fields[7].parse().ok(),
+            attributes,
+        })
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.attributes.get("ID").map(|s| s.as_str())
+    }
+
+    pub fn parents(&self) -> Vec<&str> {
+        self.attributes
+            .get("Parent")
+            .map(|s| s.split(',').collect())
+            .unwrap_or_default()
+    }
+
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(|s| s.as_str())
+    }
+
+    pub fn chromosome(&self) -> Option<YeastChromosome> {
+        YeastChromosome::from_name(&self.seqid)
+    }
+
+    pub fn genomic_range(&self) -> Option<GenomicRange> {
+        Some(GenomicRange {
+            chromosome: self.chromosome()?,
+            start: self.start,
+            end: self.end,
+        })
+    }
+}
+
+/// Decodes the `%XX` escapes GFF3 uses in column 9.
+fn unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                result.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        result.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&result).to_string()
+}
+
+/// Encodes the characters with a special meaning in column 9 (`;`, `=`, `,`,
+/// `&`, `%`) and control characters as `%XX`.
+pub(crate) fn escape(value: &str) -> String {
+    let mut result = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ';' | '=' | ',' | '&' | '%') || c.is_ascii_control() {
+            result += &format!("%{:02X}", c as u8);
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+/// All records of a GFF3 file with an index of parent/child relationships.
+#[derive(Debug, Default)]
+pub struct Gff {
+    pub records: Vec<GffRecord>,
+    children: HashMap<String, Vec<usize>>,
+}
+
+impl Gff {
+    pub fn new(records: Vec<GffRecord>) -> Self {
+        let mut children: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, record) in records.iter().enumerate() {
+            for parent in record.parents() {
+                children.entry(parent.to_string()).or_default().push(i);
+            }
+        }
+        Self { records, children }
+    }
+
+    /// Records without a parent, i.e. genes and standalone features.
+    pub fn roots(&self) -> impl Iterator<Item = &GffRecord> {
+        self.records
+            .iter()
+            .filter(|record| !record.attributes.contains_key("Parent"))
+    }
+
+    pub fn children(&self, record: &GffRecord) -> Vec<&GffRecord> {
+        record
+            .id()
+            .and_then(|id| self.children.get(id))
+            .map(|indices| indices.iter().map(|i| &self.records[*i]).collect())
+            .unwrap_or_default()
+    }
+
+    /// All records below `record` in depth-first order.
+    pub fn descendants(&self, record: &GffRecord) -> Vec<&GffRecord> {
+        let mut result = Vec::new();
+        let mut stack = self.children(record);
+        stack.reverse();
+        while let Some(next) = stack.pop() {
+            result.push(next);
+            let mut children = self.children(next);
+            children.reverse();
+            stack.extend(children);
+        }
+        result
+    }
+}
+
+/// Reads a plain or gzipped GFF3 file. An embedded `##FASTA` section ends the
+/// feature records.
+pub fn load_gff(path: &Path) -> Result<Gff> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let reader: Box<dyn Read> = if path.extension().is_some_and(|e| e == "gz") {
+        Box::new(GzDecoder::new(file))
+    } else {
+        Box::new(file)
+    };
+
+    let mut records = Vec::new();
+    for (i, line) in BufReader::new(reader).lines().enumerate() {
+        let line = line?;
+        if line.starts_with("##FASTA") {
+            break;
+        }
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        let record = GffRecord::parse(&line)
+            .with_context(|| format!("Invalid GFF3 record at line {}", i + 1))?;
+        records.push(record);
+    }
+
+    Ok(Gff::new(records))
+}
@@ -0,0 +1,318 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{bail, Context, Result};
+use flate2::read::GzDecoder;
+
+use crate::{
+    fasta::{load_fasta_records_gz, FastaType},
+    translator::{GenomicRange, Strand, YeastChromosome},
+};
+
+/// A UTR of one gene as reported by one dataset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtrCandidate {
+    pub source: String,
+    pub id: String,
+    pub is_5p: bool,
+    pub range: GenomicRange,
+    pub strand: Strand,
+    pub support: Option<f32>,
+}
+
+impl UtrCandidate {
+    pub fn len(&self) -> usize {
+        self.range.end - self.range.start + 1
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.range.end < self.range.start
+    }
+}
+
+/// Transcript ends of one gene as reported by one dataset (e.g. TIF-seq).
+#[derive(Debug, Clone, PartialEq)]
+struct TranscriptBoundary {
+    source: String,
+    id: String,
+    range: GenomicRange,
+    strand: Strand,
+    support: Option<f32>,
+}
+
+/// A pair of UTRs coming from the same dataset record.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Isoform {
+    pub source: String,
+    pub id: String,
+    pub utr5: Option<UtrCandidate>,
+    pub utr3: Option<UtrCandidate>,
+    pub support: Option<f32>,
+}
+
+impl Isoform {
+    /// Provenance label, e.g. `SGD:id001`.
+    pub fn label(&self) -> String {
+        format!("{}:{}", self.source, self.id)
+    }
+
+    fn utr_length(&self) -> usize {
+        self.utr5.as_ref().map_or(0, |u| u.len()) + self.utr3.as_ref().map_or(0, |u| u.len())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IsoformSelection {
+    /// The isoform with the highest support, falling back to `Longest`.
+    Major,
+    /// The longest 5' and the longest 3' UTR, possibly of different isoforms.
+    Longest,
+    /// Every isoform separately.
+    All,
+}
+
+impl FromStr for IsoformSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "major" => Ok(Self::Major),
+            "longest" => Ok(Self::Longest),
+            "all" => Ok(Self::All),
+            _ => Err(format!("Invalid isoform selection: {}", s)),
+        }
+    }
+}
+
+/// UTRs and transcript isoforms of many datasets, keyed by systematic name.
+#[derive(Debug, Default)]
+pub struct UtrCatalog {
+    utrs: HashMap<String, Vec<UtrCandidate>>,
+    boundaries: HashMap<String, Vec<TranscriptBoundary>>,
+}
+
+impl UtrCatalog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_utr(&mut self, systematic_name: &str, utr: UtrCandidate) {
+        self.utrs
+            .entry(systematic_name.to_string())
+            .or_default()
+            .push(utr);
+    }
+
+    /// Adds all records of a gzipped UTR FASTA file in the format of SGD's
+    /// `SGD_all_ORFs_*_UTRs.fsa`.
+    pub fn add_utr_fasta(&mut self, path: &Path, source: &str) {
+        for fasta in load_fasta_records_gz(path) {
+            if fasta.fasta_type() != FastaType::UTR {
+                continue;
+            }
+            let utr = UtrCandidate {
+                source: source.to_string(),
+                id: fasta.utr_id().unwrap_or_default(),
+                is_5p: fasta.header.contains("five_prime"),
+                range: fasta.genomic_range(),
+                strand: fasta.strand(),
+                support: None,
+            };
+            self.add_utr(&fasta.systematic_name(), utr);
+        }
+    }
+
+    /// Adds transcript isoforms from a (optionally gzipped) tab-separated table
+    /// with columns: chromosome, strand, start, end, systematic name and an
+    /// optional support value such as a read count. Lines starting with `#`
+    /// and a header line are skipped.
+    pub fn add_isoform_table(&mut self, path: &Path, source: &str) -> Result<()> {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        let reader: Box<dyn Read> = if path.extension().is_some_and(|e| e == "gz") {
+            Box::new(GzDecoder::new(file))
+        } else {
+            Box::new(file)
+        };
+
+        for (i, line) in BufReader::new(reader).lines().enumerate() {
+            let line = line?;
+            if line.starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() < 5 {
+                bail!("Expected at least 5 columns at line {}", i + 1);
+            }
+            let (Ok(start), Ok(end)) = (fields[2].parse(), fields[3].parse()) else {
+                if i == 0 {
+                    continue;
+                }
+                bail!("Invalid coordinates at line {}", i + 1);
+            };
+            let chromosome = YeastChromosome::from_name(fields[0])
+                .with_context(|| format!("Unknown chromosome at line {}", i + 1))?;
+            let strand = match fields[1] {
+                "+" => Strand::Plus,
+                "-" => Strand::Minus,
+                _ => bail!("Invalid strand at line {}", i + 1),
+            };
+
+            self.boundaries
+                .entry(fields[4].to_string())
+                .or_default()
+                .push(TranscriptBoundary {
+                    source: source.to_string(),
+                    id: format!("{}", i + 1),
+                    range: GenomicRange {
+                        chromosome,
+                        start,
+                        end,
+                    },
+                    strand,
+                    support: fields.get(5).and_then(|s| s.parse().ok()),
+                });
+        }
+        Ok(())
+    }
+
+    /// All isoforms known for a gene whose CDS (or whole feature) spans
+    /// `cds`. Transcript boundaries are split into UTRs around it.
+    pub fn isoforms(&self, systematic_name: &str, cds: &GenomicRange) -> Vec<Isoform> {
+        let mut isoforms: Vec<Isoform> = Vec::new();
+
+        for utr in self.utrs.get(systematic_name).into_iter().flatten() {
+            let index = isoforms
+                .iter()
+                .position(|i| i.source == utr.source && i.id == utr.id)
+                .unwrap_or_else(|| {
+                    isoforms.push(Isoform {
+                        source: utr.source.clone(),
+                        id: utr.id.clone(),
+                        utr5: None,
+                        utr3: None,
+                        support: utr.support,
+                    });
+                    isoforms.len() - 1
+                });
+            let slot = if utr.is_5p {
+                &mut isoforms[index].utr5
+            } else {
+                &mut isoforms[index].utr3
+            };
+            if slot.as_ref().is_none_or(|other| utr.len() > other.len()) {
+                *slot = Some(utr.clone());
+            }
+        }
+
+        for boundary in self.boundaries.get(systematic_name).into_iter().flatten() {
+            let utr = |is_5p: bool, start: usize, end: usize| {
+                (start <= end).then(|| UtrCandidate {
+                    source: boundary.source.clone(),
+                    id: boundary.id.clone(),
+                    is_5p,
+                    range: GenomicRange {
+                        chromosome: boundary.range.chromosome.clone(),
+                        start,
+                        end,
+                    },
+                    strand: boundary.strand,
+                    support: boundary.support,
+                })
+            };
+            let left = utr(
+                boundary.strand == Strand::Plus,
+                boundary.range.start,
+                cds.start.saturating_sub(1),
+            );
+            let right = utr(
+                boundary.strand == Strand::Minus,
+                cds.end + 1,
+                boundary.range.end,
+            );
+            let (utr5, utr3) = match boundary.strand {
+                Strand::Plus => (left, right),
+                Strand::Minus => (right, left),
+            };
+            isoforms.push(Isoform {
+                source: boundary.source.clone(),
+                id: boundary.id.clone(),
+                utr5,
+                utr3,
+                support: boundary.support,
+            });
+        }
+
+        isoforms
+    }
+}
+
+/// Picks the isoforms to annotate according to `selection`.
+pub fn select_isoforms(isoforms: Vec<Isoform>, selection: IsoformSelection) -> Vec<Isoform> {
+    if isoforms.is_empty() {
+        return isoforms;
+    }
+
+    match selection {
+        IsoformSelection::All => isoforms,
+        IsoformSelection::Major if isoforms.iter().any(|i| i.support.is_some()) => {
+            let major = isoforms
+                .into_iter()
+                .max_by(|a, b| {
+                    let support = |i: &Isoform| i.support.unwrap_or(f32::NEG_INFINITY);
+                    support(a)
+                        .total_cmp(&support(b))
+                        .then(a.utr_length().cmp(&b.utr_length()))
+                })
+                .unwrap();
+            vec![major]
+        }
+        IsoformSelection::Major | IsoformSelection::Longest => {
+            let longest = |utrs: Vec<&UtrCandidate>| {
+                utrs.into_iter()
+                    .fold(None::<&UtrCandidate>, |best, utr| match best {
+                        Some(best) if best.len() >= utr.len() => Some(best),
+                        _ => Some(utr),
+                    })
+                    .cloned()
+            };
+            let utr5 = longest(isoforms.iter().filter_map(|i| i.utr5.as_ref()).collect());
+            let utr3 = longest(isoforms.iter().filter_map(|i| i.utr3.as_ref()).collect());
+
+            let mut labels: Vec<(String, String)> = utr5
+                .iter()
+                .chain(utr3.iter())
+                .map(|u| (u.source.clone(), u.id.clone()))
+                .collect();
+            labels.dedup();
+            let (source, id) = if labels.len() == 1 {
+                labels.remove(0)
+            } else {
+                (
+                    labels
+                        .iter()
+                        .map(|(s, _)| s.as_str())
+                        .collect::<Vec<_>>()
+                        .join("+"),
+                    labels
+                        .iter()
+                        .map(|(_, i)| i.as_str())
+                        .collect::<Vec<_>>()
+                        .join("+"),
+                )
+            };
+            vec![Isoform {
+                source,
+                id,
+                utr5,
+                utr3,
+                support: None,
+            }]
+        }
+    }
+}
@@ -1,6 +1,27 @@
+pub mod annotation;
+pub mod chemistry;
+pub mod compare;
 pub mod data;
+pub mod diagnostics;
+pub mod export;
 pub mod fasta;
+pub mod gene;
+pub mod gff;
+pub mod isoform;
+pub mod metagene;
+pub mod model;
+pub mod normalisation;
+pub mod pipeline;
+pub mod qc;
+pub mod replicate;
+pub mod sites;
+pub mod smoothing;
+pub mod stats;
+pub mod structure;
+pub mod summary;
+pub mod transcript;
 pub mod translator;
+pub mod utr;
 pub mod tests;
 
 use flate2::read::GzDecoder;
@@ -13,7 +34,20 @@ use std::{
 pub fn load_genome_gz(path: &Path) -> (String, Vec<f32>) {
     let file = File::open(path).unwrap();
     let decoder = GzDecoder::new(file);
-    let reader = BufReader::new(decoder);
+    read_genome(BufReader::new(decoder))
+}
+
+/// Loads a profile that is gzip-compressed when its name ends in `.gz`, e.g.
+/// the input, or plain text, e.g. an annotated output.
+pub fn load_genome(path: &Path) -> (String, Vec<f32>) {
+    if path.extension().is_some_and(|e| e == "gz") {
+        return load_genome_gz(path);
+    }
+    let file = File::open(path).unwrap();
+    read_genome(BufReader::new(file))
+}
+
+fn read_genome<R: BufRead>(reader: R) -> (String, Vec<f32>) {
     let mut sequence = String::new();
     let mut profile: Vec<f32> = Vec::new();
 
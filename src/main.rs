@@ -1,6 +1,4 @@
 use std::{
-    cmp::{max, min},
-    collections::HashMap,
     fs::File,
     io::{BufRead, BufReader, BufWriter, Write},
     path::Path,
@@ -8,11 +6,21 @@ use std::{
 
 use clap::Parser;
 use flate2::read::GzDecoder;
+use rayon::prelude::*;
 
-use sgd_annotator::data::ensure_all_data;
-use sgd_annotator::fasta::{load_fasta_gz, load_utr_fasta_gz, Fasta};
-use sgd_annotator::translator::Translator;
+use sgd_annotator::annotation::{Annotations, GenomeCoordinates, SubtypePrecedence, SubtypeRules};
+use sgd_annotator::diagnostics::write_diagnostics;
+use sgd_annotator::export::{write_bed12, write_gff3, write_gtf, CoordinateSpace};
 use sgd_annotator::load_genome_gz;
+use sgd_annotator::model::{find_regions, FeatureModel};
+use sgd_annotator::normalisation::Normalisation;
+use sgd_annotator::pipeline::{annotate, CommonArgs};
+use sgd_annotator::qc::{qc_report, write_qc_json, write_qc_summary};
+use sgd_annotator::replicate::{check_sequence, correlations, merge, write_correlations};
+use sgd_annotator::smoothing::{by_segment, fill_gaps, Smoothing};
+use sgd_annotator::summary::{summarize, write_summary};
+use sgd_annotator::translator::Translator;
+use sgd_annotator::utr::{write_utr_report, UtrReport};
 
 #[derive(Parser)]
 #[command(version)]
@@ -22,331 +30,351 @@ struct Args {
 
     #[arg(short, long)]
     output: String,
-}
 
+    /// Write the feature models as BED12
+    #[arg(long)]
+    bed: Option<String>,
+
+    /// Write the feature models as GFF3
+    #[arg(long)]
+    gff3: Option<String>,
+
+    /// Write the feature models as GTF
+    #[arg(long)]
+    gtf: Option<String>,
+
+    /// Coordinates used by the feature model exports: `genome` or `input`
+    #[arg(long, default_value = "genome")]
+    coordinates: CoordinateSpace,
+
+    /// Further replicates of the input over the same genome. Their
+    /// per-position mean replaces the input profile, and mean, standard
+    /// deviation and replicate count columns are added
+    #[arg(long)]
+    replicate: Vec<String>,
+
+    /// Write Pearson and Spearman correlations of the replicates per
+    /// chromosome and per transcript
+    #[arg(long, requires = "replicate")]
+    replicate_correlations: Option<String>,
+
+    #[command(flatten)]
+    common: CommonArgs,
+
+    /// Subtypes from the highest to the lowest priority when several apply to
+    /// a position, e.g. `exon,utr5,utr3,intron`
+    #[arg(long, default_value = "intron,exon,utr3,utr5,unknown")]
+    subtype_precedence: SubtypePrecedence,
+
+    /// Write every subtype that applies to a position, separated by `;`
+    #[arg(long)]
+    all_subtypes: bool,
+
+    /// Add a column flagging positions with conflicting subtypes
+    #[arg(long)]
+    flag_conflicts: bool,
+
+    /// Label positions outside of features as intergenic, with the
+    /// neighbouring genes on either side and the distance to each
+    #[arg(long)]
+    intergenic: bool,
+
+    /// Length of the promoter windows upstream of transcript starts; implies
+    /// `--intergenic`
+    #[arg(long, default_value_t = 0)]
+    promoter_length: usize,
+
+    /// Add chromosome, genomic position, reference base and sense base
+    /// columns to every track
+    #[arg(long)]
+    genomic_columns: bool,
+
+    /// Add transcript position, offsets from the start and stop codons and
+    /// exon or intron number columns to every track
+    #[arg(long)]
+    transcript_columns: bool,
+
+    /// Add normalised profile columns, each given as `METHOD[:SCOPE]`.
+    /// Methods are `2-8`, `boxplot`, `90th` and `winsorise`; scopes are
+    /// `genome`, `chromosome` and `transcript`
+    #[arg(long, value_delimiter = ',')]
+    normalise: Vec<Normalisation>,
+
+    /// Normalise only these bases, each on its own, e.g. `AC` for DMS
+    #[arg(long)]
+    normalise_bases: Option<String>,
+
+    /// Add smoothed profile columns, each given as `KERNEL[:WINDOW]`.
+    /// Kernels are `mean`, `median` and `gaussian`; the window defaults to
+    /// 5 nt. Exons and introns of each feature are smoothed separately
+    #[arg(long, value_delimiter = ',')]
+    smooth: Vec<Smoothing>,
+
+    /// Add a column with gaps of at most this many missing values filled by
+    /// linear interpolation within each exon or intron. Smoothing is then
+    /// applied to the filled profile
+    #[arg(long)]
+    fill_gaps: Option<usize>,
+
+    /// Write reactivity statistics of every UTR, CDS, exon and intron
+    #[arg(long)]
+    summary: Option<String>,
+
+    /// Write every feature that was skipped or altered, with a reason code
+    #[arg(long)]
+    diagnostics: Option<String>,
+
+    /// Check the input profile and write the report as JSON; a summary is
+    /// printed to the terminal
+    #[arg(long)]
+    qc: Option<String>,
+
+    /// Shortest run of missing values listed by the QC report
+    #[arg(long, default_value_t = 100)]
+    min_nan_run: usize,
+
+    /// Write the original and refined range of every UTR
+    #[arg(long)]
+    utr_report: Option<String>,
+}
 
-fn translate_all(
-    all_genomic: &Vec<(&String, &Fasta)>,
-    utr5p: &HashMap<String, Fasta>,
-    utr3p: &HashMap<String, Fasta>,
-    translator: &Translator,
-    profile: &Vec<f32>,
-) -> HashMap<String, (usize, usize)> {
-    let mut ranges = HashMap::new();
-
-    for (name, fasta) in all_genomic {
-        if let Some((mut start, mut end)) =
-            translator.translate_genomic_range(&fasta.genomic_range())
-        {
-            if let Some(utr) = utr5p.get(*name) {
-                if let Some((utr_start, utr_end)) =
-                    dynamically_fix_range_for_utr(utr, translator, profile)
-                {
-                    start = min(start, utr_start);
-                    end = max(end, utr_end);
-                }
-            }
-
-            if let Some(utr) = utr3p.get(*name) {
-                if let Some((utr_start, utr_end)) =
-                    dynamically_fix_range_for_utr(utr, translator, profile)
-                {
-                    start = min(start, utr_start);
-                    end = max(end, utr_end);
-                }
-            }
+/// Number of input lines formatted by a single task when writing the result.
+const CHUNK_SIZE: usize = 1 << 16;
 
-            ranges.insert((*name).to_string(), (start, end));
-        }
-    }
-
-    ranges
+/// Values of a column written after the input columns.
+enum ColumnValues {
+    Numbers(Vec<f32>),
+    Counts(Vec<usize>),
+    Flags(Vec<bool>),
 }
 
-fn create_graph(
-    all_genomic: &Vec<(&String, &Fasta)>,
-    ranges: &HashMap<String, (usize, usize)>,
-) -> HashMap<String, Vec<String>> {
-    let names: Vec<&String> = all_genomic.iter().map(|(name, _)| *name).collect();
-    let mut graph = HashMap::new();
-
-    for i in 0..names.len() {
-        let name_i = names.get(i).unwrap().to_string();
-
-        if let Some((start_i, end_i)) = ranges.get(&name_i) {
-            for j in (i + 1)..names.len() {
-                let name_j = names.get(j).unwrap().to_string();
-
-                if let Some((start_j, end_j)) = ranges.get(&name_j) {
-                    if (start_i < start_j && start_j < end_i)
-                        || (start_i < end_j && end_j < end_i)
-                        || (start_j < start_i && start_i < end_j)
-                        || (start_j < end_i && end_i < end_j)
-                    {
-                        if !graph.contains_key(&name_i) {
-                            graph.insert(name_i.clone(), Vec::new());
-                        }
-                        graph.get_mut(&name_i).unwrap().push(name_j.clone());
-
-                        if !graph.contains_key(&name_j) {
-                            graph.insert(name_j.clone(), Vec::new());
-                        }
-                        graph.get_mut(&name_j).unwrap().push(name_i.clone());
-                    }
-                }
-            }
-        }
-    }
-
-    graph
+struct ExtraColumn {
+    name: String,
+    values: ColumnValues,
 }
 
-fn determine_order(
-    all_genomic: &Vec<(&String, &Fasta)>,
-    graph: &HashMap<String, Vec<String>>,
-) -> HashMap<String, usize> {
-    let mut colors: HashMap<String, usize> = HashMap::new();
+fn store_result(input: &Path, output: &Path, extra: &[ExtraColumn], annotations: &Annotations) {
+    let fin = File::open(input).unwrap();
+    let fout = File::create(output).unwrap();
+    let decoder = GzDecoder::new(fin);
+    let reader = BufReader::new(decoder);
+    let mut writer = BufWriter::new(fout);
 
-    for (current, _) in all_genomic.iter() {
-        let mut available = [true, true, true, true, true, true, true, true, true, true];
+    let mut lines = reader.lines().enumerate();
 
-        if let Some(adjacent) = graph.get(*current) {
-            for next in adjacent {
-                if let Some(color) = colors.get(next) {
-                    available[*color] = false;
-                }
-            }
+    if let Some((_, Ok(line))) = lines.next() {
+        let _ = writer.write(line.as_bytes());
+        for column in extra {
+            let _ = writer.write(b"\t");
+            let _ = writer.write(column.name.as_bytes());
         }
-
-        for i in 0..available.len() {
-            if available[i] {
-                colors.insert(current.to_string(), i);
-                break;
-            }
+        for name in annotations.column_names() {
+            let _ = writer.write(b"\t");
+            let _ = writer.write(name.as_bytes());
         }
+        let _ = writer.write("\n".as_bytes());
     }
 
-    colors
-}
-
-fn fill_annotations(
-    genome: &str,
-    all_genomic: &Vec<(&String, &Fasta)>,
-    orf_genomic: &HashMap<String, Fasta>,
-    rna_genomic: &HashMap<String, Fasta>,
-    utr5p: &HashMap<String, Fasta>,
-    utr3p: &HashMap<String, Fasta>,
-    orf_coding: &HashMap<String, Fasta>,
-    rna_coding: &HashMap<String, Fasta>,
-    ranges: &HashMap<String, (usize, usize)>,
-    orders: &HashMap<String, usize>,
-    translator: &Translator,
-    profile: &Vec<f32>,
-) -> Vec<Vec<String>> {
-    let max = orders.values().max().unwrap();
-    let mut annotations: Vec<Vec<String>> = Vec::with_capacity(genome.len());
-    for _ in 0..genome.len() {
-        let mut v = Vec::with_capacity((*max + 1) * 4);
-        for _ in 0..(*max + 1) * 4 {
-            v.push(String::new());
+    // Read a bounded batch of lines, format its chunks in parallel and write
+    // them back in input order.
+    let batch_size = CHUNK_SIZE * rayon::current_num_threads();
+    loop {
+        let batch: Vec<(usize, std::io::Result<String>)> =
+            lines.by_ref().take(batch_size).collect();
+        if batch.is_empty() {
+            break;
         }
-        annotations.push(v);
-    }
 
-    for (name, fasta) in all_genomic.iter() {
-        let category = if orf_genomic.contains_key(*name) {
-            "ORF"
-        } else if rna_genomic.contains_key(*name) {
-            "RNA"
-        } else {
-            "Other"
-        };
-
-        let order = orders.get(*name).unwrap();
-        let (start, end) = ranges.get(*name).unwrap();
-        for i in *start..=*end {
-            annotations[i][*order * 4] = category.to_string();
-            annotations[i][*order * 4 + 1] = "?".to_string();
-            annotations[i][*order * 4 + 2] = fasta.systematic_name().to_string();
-            annotations[i][*order * 4 + 3] = fasta.standard_name().to_string();
+        let formatted: Vec<String> = batch
+            .par_chunks(CHUNK_SIZE)
+            .map(|chunk| format_chunk(chunk, extra, annotations))
+            .collect();
+        for text in formatted {
+            let _ = writer.write(text.as_bytes());
         }
+    }
+}
 
-        if category == "ORF" {
-            if let Some(fasta) = utr5p.get(*name) {
-                if let Some((start, end)) =
-                    dynamically_fix_range_for_utr(fasta, translator, profile)
-                {
-                    for i in start..=end {
-                        annotations[i][*order * 4 + 1] = "UTR 5'".to_string();
-                    }
-                }
-            }
-
-            if let Some(fasta) = utr3p.get(*name) {
-                if let Some((start, end)) =
-                    dynamically_fix_range_for_utr(fasta, translator, profile)
-                {
-                    for i in start..=end {
-                        annotations[i][*order * 4 + 1] = "UTR 3'".to_string();
-                    }
-                }
-            }
+fn format_chunk(
+    chunk: &[(usize, std::io::Result<String>)],
+    extra: &[ExtraColumn],
+    annotations: &Annotations,
+) -> String {
+    let mut cursor = annotations.cursor();
+    let mut text = String::new();
 
-            if let Some(fasta) = orf_coding.get(*name) {
-                for range in fasta.coding_ranges().unwrap().iter() {
-                    let (start, end) = translator.translate_genomic_range(range).unwrap();
-                    for i in start..=end {
-                        annotations[i][*order * 4 + 1] = "Exon".to_string();
-                    }
-                }
-                if fasta.noncoding_ranges().is_some() {
-                    for range in fasta.noncoding_ranges().unwrap().iter() {
-                        let (start, end) = translator.translate_genomic_range(range).unwrap();
-                        for i in start..=end {
-                            annotations[i][*order * 4 + 1] = "Intron".to_string();
-                        }
+    for (i, line) in chunk {
+        if let Ok(line) = line {
+            text += line;
+            for column in extra {
+                match &column.values {
+                    ColumnValues::Numbers(values) => match values.get(i - 1) {
+                        Some(value) if !value.is_nan() => text += &format!("\t{:.4}", value),
+                        _ => text += "\tNaN",
+                    },
+                    ColumnValues::Counts(counts) => {
+                        text += &format!("\t{}", counts.get(i - 1).unwrap_or(&0))
                     }
+                    ColumnValues::Flags(flags) => match flags.get(i - 1) {
+                        Some(true) => text += "\t1",
+                        _ => text += "\t0",
+                    },
                 }
             }
-        } else if category == "RNA" {
-            if let Some(fasta) = rna_coding.get(*name) {
-                for range in fasta.coding_ranges().unwrap().iter() {
-                    let (start, end) = translator.translate_genomic_range(range).unwrap();
-                    for i in start..=end {
-                        annotations[i][*order * 4 + 1] = "Exon".to_string();
-                    }
-                }
-                if fasta.noncoding_ranges().is_some() {
-                    for range in fasta.noncoding_ranges().unwrap().iter() {
-                        let (start, end) = translator.translate_genomic_range(range).unwrap();
-                        for i in start..=end {
-                            annotations[i][*order * 4 + 1] = "Intron".to_string();
-                        }
-                    }
-                }
+            for annotation in cursor.row(i - 1) {
+                text += "\t";
+                text += &annotation;
             }
+            text += "\n";
         }
     }
 
-    annotations
+    text
 }
 
-fn store_result(input: &Path, output: &Path, annotations: Vec<Vec<String>>) {
-    let fin = File::open(input).unwrap();
-    let fout = File::create(output).unwrap();
-    let decoder = GzDecoder::new(fin);
-    let reader = BufReader::new(decoder);
-    let mut writer = BufWriter::new(fout);
+fn main() {
+    let args = Args::parse();
 
-    let count = annotations.get(0).unwrap().len();
+    args.common.setup().unwrap();
 
-    for (i, line) in reader.lines().enumerate() {
-        if let Ok(line) = line {
-            let _ = writer.write(line.as_bytes());
-
-            if i == 0 {
-                for j in 0..count / 4 {
-                    let _ = writer.write(
-                        format!(
-                            "\tType {}\tSubtype {}\tSystematic name {}\tStandard name {}",
-                            j + 1,
-                            j + 1,
-                            j + 1,
-                            j + 1
-                        )
-                        .as_bytes(),
-                    );
-                }
-                let _ = writer.write("\n".as_bytes());
-            } else {
-                for annotation in annotations.get(i - 1).unwrap().iter() {
-                    let _ = writer.write(b"\t");
-                    let _ = writer.write(annotation.as_bytes());
-                }
-                let _ = writer.write(b"\n");
-            }
+    let (genome, mut profile) = load_genome_gz(Path::new(&args.input));
+    let translator = Translator::new(&genome);
+
+    let mut replicates = None;
+    if !args.replicate.is_empty() {
+        let mut names = vec![args.input.clone()];
+        let mut profiles = vec![profile];
+        for path in args.replicate.iter() {
+            let (sequence, values) = load_genome_gz(Path::new(path));
+            check_sequence(&genome, &sequence, path).unwrap();
+            names.push(path.clone());
+            profiles.push(values);
         }
+        let merged = merge(&profiles);
+        profile = merged.mean.clone();
+        replicates = Some((names, profiles, merged));
     }
-}
 
-fn dynamically_fix_range_for_utr(
-    fasta: &Fasta,
-    translator: &Translator,
-    profile: &Vec<f32>,
-) -> Option<(usize, usize)> {
-    let range = fasta.genomic_range();
-
-    if let Some((start, end)) = translator.translate_genomic_range(&range) {
-        let is_5p = fasta.header.contains("five_prime");
-        let is_plus = fasta.header.contains("strand=+");
-
-        if (is_5p && is_plus) || (!is_5p && !is_plus) {
-            for i in (start..=end).rev() {
-                let slice = &profile[i - 6..i];
-                if slice.iter().all(|x| x.is_nan()) {
-                    return Some((i, end));
-                }
-            }
-        } else {
-            for i in start..=end {
-                let slice = &profile[i + 1..i + 7];
-                if slice.iter().all(|x| x.is_nan()) {
-                    return Some((start, i));
-                }
-            }
-        }
+    let (models, diagnostics) = args.common.models.build(&translator, &profile).unwrap();
+    // UTRs are refined on the raw profile, statistics use the probed one.
+    let probed = args.common.probe.apply(&genome, &profile);
+    let (models, reports): (Vec<FeatureModel>, Vec<Vec<UtrReport>>) = models.into_iter().unzip();
+
+    if let Some(path) = &args.diagnostics {
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        write_diagnostics(&mut writer, &diagnostics).unwrap();
     }
 
-    None
-}
+    if let Some(path) = &args.qc {
+        let report = qc_report(&probed, &translator, &models, args.min_nan_run);
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        write_qc_json(&mut writer, &report).unwrap();
+        write_qc_summary(&mut std::io::stdout().lock(), &report).unwrap();
+    }
 
-fn main() {
-    let args = Args::parse();
+    if let Some(path) = &args.utr_report {
+        let reports: Vec<UtrReport> = reports.into_iter().flatten().collect();
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        write_utr_report(&mut writer, &reports).unwrap();
+    }
 
-    // Ensure all data files are present
-    ensure_all_data().unwrap();
+    let mut annotations = annotate(&models).unwrap();
+    annotations.set_subtype_rules(SubtypeRules {
+        precedence: args.subtype_precedence.clone(),
+        all_subtypes: args.all_subtypes,
+        flag_conflicts: args.flag_conflicts,
+    });
+    annotations.set_transcript_columns(args.transcript_columns);
+    if args.genomic_columns {
+        annotations.set_coordinates(GenomeCoordinates::new(&translator, genome.clone()));
+    }
+    if args.intergenic || args.promoter_length > 0 {
+        annotations.set_regions(find_regions(&models, &translator, args.promoter_length));
+    }
 
-    let (genome, profile) = load_genome_gz(Path::new(&args.input));
-    let translator = Translator::new(&genome);
+    let mut models = models;
+    models.sort_by(|a, b| (&a.chromosome, a.range).cmp(&(&b.chromosome, b.range)));
+
+    if let Some(path) = &args.bed {
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        write_bed12(&mut writer, &models, &translator, args.coordinates).unwrap();
+    }
+    if let Some(path) = &args.gff3 {
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        write_gff3(&mut writer, &models, &translator, args.coordinates).unwrap();
+    }
+    if let Some(path) = &args.gtf {
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        write_gtf(&mut writer, &models, &translator, args.coordinates).unwrap();
+    }
+    if let Some(path) = &args.summary {
+        let summaries = summarize(&models, &translator, &genome, &probed).unwrap();
+        let mut writer = BufWriter::new(File::create(path).unwrap());
+        write_summary(&mut writer, &summaries).unwrap();
+    }
+
+    let mut extra = Vec::new();
+    if let Some((names, profiles, merged)) = replicates {
+        if let Some(path) = &args.replicate_correlations {
+            let profiles: Vec<Vec<f32>> = profiles
+                .iter()
+                .map(|profile| args.common.probe.apply(&genome, profile))
+                .collect();
+            let correlations = correlations(&names, &profiles, &translator, &models);
+            let mut writer = BufWriter::new(File::create(path).unwrap());
+            write_correlations(&mut writer, &correlations).unwrap();
+        }
+        extra.push(ExtraColumn {
+            name: "Replicate mean".to_string(),
+            values: ColumnValues::Numbers(merged.mean),
+        });
+        extra.push(ExtraColumn {
+            name: "Replicate SD".to_string(),
+            values: ColumnValues::Numbers(merged.sd),
+        });
+        extra.push(ExtraColumn {
+            name: "Replicates".to_string(),
+            values: ColumnValues::Counts(merged.count),
+        });
+    }
+    extra.extend(args.normalise.iter().map(|normalisation| ExtraColumn {
+        name: normalisation.column_name(),
+        values: ColumnValues::Numbers(normalisation.apply(
+            &probed,
+            &genome,
+            args.normalise_bases.as_deref(),
+            &translator,
+            &models,
+        )),
+    }));
+    let filled = args
+        .fill_gaps
+        .map(|max_gap| by_segment(&probed, &models, |segment| fill_gaps(segment, max_gap)));
+    extra.extend(args.smooth.iter().map(|smoothing| ExtraColumn {
+        name: smoothing.column_name(),
+        values: ColumnValues::Numbers(by_segment(
+            filled.as_deref().unwrap_or(&probed),
+            &models,
+            |segment| smoothing.smooth(segment),
+        )),
+    }));
+    if let (Some(max_gap), Some(filled)) = (args.fill_gaps, filled) {
+        extra.push(ExtraColumn {
+            name: format!("Gaps filled (up to {} nt)", max_gap),
+            values: ColumnValues::Numbers(filled),
+        });
+    }
+    if args.common.probe.flag_unreactive {
+        extra.push(ExtraColumn {
+            name: "Reactive base".to_string(),
+            values: ColumnValues::Flags(args.common.probe.chemistry.reactive(&genome)),
+        });
+    }
 
-    let orf_genomic = load_fasta_gz(Path::new("data/orf_genomic.fasta.gz"));
-    let rna_genomic = load_fasta_gz(Path::new("data/rna_genomic.fasta.gz"));
-    let other_genomic = load_fasta_gz(Path::new("data/other_features_genomic.fasta.gz"));
-
-    let all_genomic: Vec<(&String, &Fasta)> = orf_genomic
-        .iter()
-        .chain(rna_genomic.iter())
-        .chain(other_genomic.iter())
-        .filter(|(_, fasta)| {
-            translator
-                .translate_genomic_range(&fasta.genomic_range())
-                .is_some()
-        })
-        .collect();
-
-    let orf_coding = load_fasta_gz(Path::new("data/orf_coding.fasta.gz"));
-    let rna_coding = load_fasta_gz(Path::new("data/rna_coding.fasta.gz"));
-
-    let utr5p = load_utr_fasta_gz(Path::new("data/5prime_utr.fsa.gz"));
-    let utr3p = load_utr_fasta_gz(Path::new("data/3prime_utr.fsa.gz"));
-
-    let ranges = translate_all(&all_genomic, &utr5p, &utr3p, &translator, &profile);
-    let graph = create_graph(&all_genomic, &ranges);
-    let orders = determine_order(&all_genomic, &graph);
-
-    let annotations = fill_annotations(
-        &genome,
-        &all_genomic,
-        &orf_genomic,
-        &rna_genomic,
-        &utr5p,
-        &utr3p,
-        &orf_coding,
-        &rna_coding,
-        &ranges,
-        &orders,
-        &translator,
-        &profile,
+    store_result(
+        Path::new(&args.input),
+        Path::new(&args.output),
+        &extra,
+        &annotations,
     );
-    store_result(Path::new(&args.input), Path::new(&args.output), annotations);
 }
@@ -0,0 +1,309 @@
+use std::{collections::BTreeMap, fmt::Display, io::Write, str::FromStr};
+
+use anyhow::Result;
+
+use crate::{
+    model::FeatureModel,
+    stats::{mean, median, number, sd, sorted},
+    translator::Strand,
+};
+
+/// A position that metagene profiles are aligned on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Landmark {
+    /// First nucleotide of the start codon.
+    StartCodon,
+    /// First nucleotide of the stop codon.
+    StopCodon,
+    /// First nucleotide of an intron.
+    SpliceDonor,
+    /// Last nucleotide of an intron.
+    SpliceAcceptor,
+    /// First nucleotide of a transcript with a 5' UTR.
+    Tss,
+    /// Last nucleotide of a transcript with a 3' UTR.
+    Pas,
+}
+
+impl FromStr for Landmark {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "start" => Ok(Self::StartCodon),
+            "stop" => Ok(Self::StopCodon),
+            "donor" => Ok(Self::SpliceDonor),
+            "acceptor" => Ok(Self::SpliceAcceptor),
+            "tss" => Ok(Self::Tss),
+            "pas" => Ok(Self::Pas),
+            _ => Err(format!("Invalid landmark: {}", s)),
+        }
+    }
+}
+
+impl Display for Landmark {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::StartCodon => write!(f, "Start codon"),
+            Self::StopCodon => write!(f, "Stop codon"),
+            Self::SpliceDonor => write!(f, "Splice donor"),
+            Self::SpliceAcceptor => write!(f, "Splice acceptor"),
+            Self::Tss => write!(f, "TSS"),
+            Self::Pas => write!(f, "PAS"),
+        }
+    }
+}
+
+impl Landmark {
+    /// Input indices of this landmark in `model`.
+    pub fn positions(&self, model: &FeatureModel) -> Vec<usize> {
+        let plus = model.strand == Strand::Plus;
+        let five_prime = |(start, end): (usize, usize)| if plus { start } else { end };
+        let three_prime = |(start, end): (usize, usize)| if plus { end } else { start };
+        match self {
+            Self::StartCodon => model.cds_range().map(five_prime).into_iter().collect(),
+            Self::StopCodon => model
+                .cds_range()
+                .filter(|(start, end)| end - start >= 2)
+                .map(|(start, end)| if plus { end - 2 } else { start + 2 })
+                .into_iter()
+                .collect(),
+            Self::SpliceDonor => model.introns.iter().map(|i| five_prime(*i)).collect(),
+            Self::SpliceAcceptor => model.introns.iter().map(|i| three_prime(*i)).collect(),
+            Self::Tss => model
+                .utr5
+                .map(|_| five_prime(model.range))
+                .into_iter()
+                .collect(),
+            Self::Pas => model
+                .utr3
+                .map(|_| three_prime(model.range))
+                .into_iter()
+                .collect(),
+        }
+    }
+}
+
+/// How the values of one gene are scaled before aggregation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GeneNormalisation {
+    None,
+    /// Divide by the mean of the gene's values in the window.
+    Mean,
+    /// Divide by the maximum of the gene's values in the window.
+    Max,
+}
+
+impl FromStr for GeneNormalisation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "mean" => Ok(Self::Mean),
+            "max" => Ok(Self::Max),
+            _ => Err(format!("Invalid normalisation: {}", s)),
+        }
+    }
+}
+
+impl GeneNormalisation {
+    /// Scales `values` in place. Returns `false` when the gene carries no
+    /// usable signal and should be left out.
+    fn apply(&self, values: &mut [f32]) -> bool {
+        let present: Vec<f32> = values.iter().copied().filter(|x| !x.is_nan()).collect();
+        if present.is_empty() {
+            return false;
+        }
+        let factor = match self {
+            Self::None => return true,
+            Self::Mean => mean(&present),
+            Self::Max => present.iter().copied().fold(f32::MIN, f32::max),
+        };
+        if factor <= 0.0 {
+            return false;
+        }
+        values.iter_mut().for_each(|x| *x /= factor);
+        true
+    }
+}
+
+/// Aggregated values at one position of a metagene curve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurvePoint {
+    /// Offset from the landmark, or bin number of a scaled region.
+    pub position: isize,
+    /// Number of genes with a value at this position.
+    pub genes: usize,
+    pub mean: f32,
+    pub median: f32,
+    /// Bounds of the normal-approximation 95% confidence interval of the
+    /// mean, NaN with fewer than two genes.
+    pub ci_low: f32,
+    pub ci_high: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    pub label: String,
+    pub points: Vec<CurvePoint>,
+}
+
+impl Curve {
+    /// Aggregates equally long `rows` column by column. The rows of a gene,
+    /// from its isoforms or repeated landmarks, are first averaged into one
+    /// so that every gene weighs the same.
+    fn from_rows(label: String, positions: Vec<isize>, rows: Vec<(&str, Vec<f32>)>) -> Self {
+        let mut genes: BTreeMap<&str, Vec<Vec<f32>>> = BTreeMap::new();
+        for (name, row) in rows {
+            genes.entry(name).or_default().push(row);
+        }
+        let rows: Vec<Vec<f32>> = genes
+            .into_values()
+            .map(|rows| {
+                (0..positions.len())
+                    .map(|j| mean(&sorted(&rows.iter().map(|row| row[j]).collect::<Vec<_>>())))
+                    .collect()
+            })
+            .collect();
+
+        let points = positions
+            .into_iter()
+            .enumerate()
+            .map(|(j, position)| {
+                let values = sorted(&rows.iter().map(|row| row[j]).collect::<Vec<_>>());
+                let n = values.len();
+                let mean = mean(&values);
+                let margin = sd(&values).map_or(f32::NAN, |sd| 1.96 * sd / (n as f32).sqrt());
+                let median = median(&values);
+                CurvePoint {
+                    position,
+                    genes: n,
+                    mean,
+                    median,
+                    ci_low: mean - margin,
+                    ci_high: mean + margin,
+                }
+            })
+            .collect();
+        Self { label, points }
+    }
+}
+
+/// Profile values at `indices`, NaN outside of the profile.
+fn values_at(indices: impl Iterator<Item = usize>, profile: &[f32]) -> Vec<f32> {
+    indices
+        .map(|i| profile.get(i).copied().unwrap_or(f32::NAN))
+        .collect()
+}
+
+/// Aligns the profile on every occurrence of `landmark`, `flank` nucleotides
+/// to either side in the direction of transcription.
+pub fn landmark_curve(
+    models: &[FeatureModel],
+    landmark: Landmark,
+    flank: usize,
+    normalisation: GeneNormalisation,
+    profile: &[f32],
+) -> Curve {
+    let offsets: Vec<isize> = (-(flank as isize)..=flank as isize).collect();
+    let mut rows = Vec::new();
+    for model in models {
+        for position in landmark.positions(model) {
+            let indices = offsets.iter().map(|k| {
+                let index = match model.strand {
+                    Strand::Plus => position as isize + k,
+                    Strand::Minus => position as isize - k,
+                };
+                usize::try_from(index).unwrap_or(usize::MAX)
+            });
+            let mut row = values_at(indices, profile);
+            if normalisation.apply(&mut row) {
+                rows.push((model.systematic_name.as_str(), row));
+            }
+        }
+    }
+    Curve::from_rows(landmark.to_string(), offsets, rows)
+}
+
+/// Splits the spliced positions of `intervals`, in the direction of
+/// transcription, into `bins` equal parts and averages each of them.
+fn binned(intervals: &[(usize, usize)], strand: Strand, bins: usize, profile: &[f32]) -> Vec<f32> {
+    let mut indices: Vec<usize> = intervals.iter().flat_map(|(s, e)| *s..=*e).collect();
+    if strand == Strand::Minus {
+        indices.reverse();
+    }
+    (0..bins)
+        .map(|b| {
+            let from = b * indices.len() / bins;
+            let to = ((b + 1) * indices.len() / bins).max(from + 1);
+            let values = values_at(
+                indices[from..to.min(indices.len())].iter().copied(),
+                profile,
+            );
+            mean(&sorted(&values))
+        })
+        .collect()
+}
+
+/// Scales the 5' UTR, CDS and 3' UTR of every ORF to `bins` bins each so
+/// that regions of different lengths line up. Only ORFs with both UTRs are
+/// used.
+pub fn scaled_curves(
+    models: &[FeatureModel],
+    bins: usize,
+    normalisation: GeneNormalisation,
+    profile: &[f32],
+) -> Vec<Curve> {
+    let mut rows: [Vec<(&str, Vec<f32>)>; 3] = Default::default();
+    for model in models {
+        let (Some(utr5), Some(utr3)) = (model.utr5, model.utr3) else {
+            continue;
+        };
+        if !model.is_coding() || bins == 0 {
+            continue;
+        }
+        let regions = [vec![utr5], model.exons.clone(), vec![utr3]];
+        let mut row: Vec<f32> = regions
+            .iter()
+            .flat_map(|intervals| binned(intervals, model.strand, bins, profile))
+            .collect();
+        if !normalisation.apply(&mut row) {
+            continue;
+        }
+        for (r, chunk) in row.chunks(bins).enumerate() {
+            rows[r].push((model.systematic_name.as_str(), chunk.to_vec()));
+        }
+    }
+
+    ["UTR 5'", "CDS", "UTR 3'"]
+        .iter()
+        .zip(rows)
+        .map(|(label, rows)| {
+            Curve::from_rows(label.to_string(), (1..=bins as isize).collect(), rows)
+        })
+        .collect()
+}
+
+pub fn write_curves<W: Write>(writer: &mut W, curves: &[Curve]) -> Result<()> {
+    writeln!(
+        writer,
+        "Curve\tPosition\tGenes\tMean\tMedian\tCI low\tCI high"
+    )?;
+    for curve in curves {
+        for point in &curve.points {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                curve.label,
+                point.position,
+                point.genes,
+                number(point.mean),
+                number(point.median),
+                number(point.ci_low),
+                number(point.ci_high)
+            )?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+
+use rayon::prelude::*;
+
+use crate::{
+    annotation::Regions,
+    diagnostics::{Diagnostic, Reason},
+    gene::{Gene, Transcript, Utr},
+    translator::{Strand, Translator, YeastChromosome},
+    utr::{UtrBoundaryStrategy, UtrRegion, UtrReport},
+};
+
+/// A transcript as laid out by the annotator. All ranges are closed intervals in
+/// input index space.
+#[derive(Debug, Clone)]
+pub struct FeatureModel {
+    pub systematic_name: String,
+    pub standard_name: String,
+    /// SGD feature type in readable form, e.g. `ORF`, `tRNA`, `ARS`.
+    pub category: String,
+    /// ORF classification: `Verified`, `Uncharacterized` or `Dubious`.
+    pub qualifier: Option<String>,
+    /// Whether `exons` are CDS pieces rather than exons of a noncoding RNA.
+    pub coding: bool,
+    pub chromosome: YeastChromosome,
+    pub strand: Strand,
+    /// Genomic range extended by the trimmed UTRs.
+    pub range: (usize, usize),
+    /// Coding ranges (CDS pieces of ORFs, exons of RNAs), sorted.
+    pub exons: Vec<(usize, usize)>,
+    pub introns: Vec<(usize, usize)>,
+    pub utr5: Option<(usize, usize)>,
+    pub utr3: Option<(usize, usize)>,
+    /// Whether `utr5` and `utr3` were inferred rather than observed.
+    pub utr5_inferred: bool,
+    pub utr3_inferred: bool,
+    /// Provenance of the UTRs, e.g. `SGD:id001`.
+    pub isoform: Option<String>,
+}
+
+impl FeatureModel {
+    /// Unique key of the model: the systematic name, qualified by the isoform
+    /// when one is set.
+    pub fn id(&self) -> String {
+        match &self.isoform {
+            Some(isoform) => format!("{}#{}", self.systematic_name, isoform),
+            None => self.systematic_name.clone(),
+        }
+    }
+
+    /// Transcript identifier used in exported annotations.
+    pub fn transcript_id(&self) -> String {
+        match &self.isoform {
+            Some(isoform) => format!("{}_{}", self.systematic_name, isoform),
+            None => format!("{}_mRNA", self.systematic_name),
+        }
+    }
+
+    pub fn is_coding(&self) -> bool {
+        self.coding && !self.exons.is_empty()
+    }
+
+    /// Span from the first to the last coding nucleotide of an ORF.
+    pub fn cds_range(&self) -> Option<(usize, usize)> {
+        if !self.is_coding() {
+            return None;
+        }
+        Some((self.exons.first()?.0, self.exons.last()?.1))
+    }
+
+    /// Exonic blocks of the mature transcript: the exons with the outermost
+    /// ones stretched to the ends of `range`. Touching or overlapping exons,
+    /// as the CDS pieces at ribosomal frameshifts, form a single block so
+    /// that no base is spliced in twice.
+    pub fn blocks(&self) -> Vec<(usize, usize)> {
+        if self.exons.is_empty() {
+            return vec![self.range];
+        }
+        let mut blocks: Vec<(usize, usize)> = Vec::new();
+        for &(start, end) in &self.exons {
+            match blocks.last_mut() {
+                Some(last) if start <= last.1 + 1 => last.1 = last.1.max(end),
+                _ => blocks.push((start, end)),
+            }
+        }
+        let last = blocks.len() - 1;
+        blocks[0].0 = blocks[0].0.min(self.range.0);
+        blocks[last].1 = blocks[last].1.max(self.range.1);
+        blocks
+    }
+
+    /// Lays out `transcript` of `gene` in input index space, refining its UTRs
+    /// with `strategy`. Returns `None` when the gene is not part of the input.
+    /// Skipped genes and dropped UTRs are added to `diagnostics`.
+    pub fn from_transcript(
+        gene: &Gene,
+        transcript: &Transcript,
+        translator: &Translator,
+        strategy: &dyn UtrBoundaryStrategy,
+        profile: &[f32],
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<(Self, Vec<UtrReport>)> {
+        let mut report = |reason: Reason, detail: String| {
+            diagnostics.push(
+                Diagnostic::new(&gene.systematic_name, reason, detail)
+                    .with_isoform(transcript.isoform.as_deref()),
+            );
+        };
+        let Some(length) = translator.chromosome_length(&gene.chromosome) else {
+            report(
+                Reason::Unmapped,
+                format!("chr{} is not part of the input", gene.chromosome),
+            );
+            return None;
+        };
+        if gene.start == 0 || gene.end > length {
+            report(
+                Reason::OffEdge,
+                format!(
+                    "{}-{} outside of chr{} (1-{})",
+                    gene.start, gene.end, gene.chromosome, length
+                ),
+            );
+            return None;
+        }
+        let range = translator.translate_genomic_range(&gene.genomic_range())?;
+        let translate = |start: usize, end: usize| {
+            Some((
+                translator.translate_nt(&gene.chromosome, start)?,
+                translator.translate_nt(&gene.chromosome, end)?,
+            ))
+        };
+
+        let mut reports = Vec::new();
+        let mut refine = |utr: &Option<Utr>, is_5p: bool| {
+            let utr = utr.as_ref()?;
+            if utr.start == 0 || utr.end > length {
+                report(
+                    Reason::UtrOffEdge,
+                    format!(
+                        "{} UTR {}-{} outside of chr{} (1-{})",
+                        if is_5p { "5'" } else { "3'" },
+                        utr.start,
+                        utr.end,
+                        gene.chromosome,
+                        length
+                    ),
+                );
+                return None;
+            }
+            let (start, end) = translate(utr.start, utr.end)?;
+            let region = UtrRegion {
+                start,
+                end,
+                is_5p,
+                strand: gene.strand,
+            };
+            let refinement = strategy.refine(&region, profile);
+            let range = refinement.range;
+            reports.push(UtrReport {
+                systematic_name: gene.systematic_name.clone(),
+                source: utr.source.clone(),
+                utr: region,
+                refinement,
+            });
+            range
+        };
+        let utr5 = refine(&transcript.utr5, true);
+        let utr3 = refine(&transcript.utr3, false);
+
+        let mut model = Self {
+            systematic_name: gene.systematic_name.clone(),
+            standard_name: gene.standard_name.clone(),
+            category: gene.category.clone(),
+            qualifier: gene.qualifier.clone(),
+            coding: gene.coding,
+            chromosome: gene.chromosome.clone(),
+            strand: gene.strand,
+            range,
+            exons: transcript
+                .exons
+                .iter()
+                .filter_map(|e| translate(e.start, e.end))
+                .collect(),
+            introns: transcript
+                .introns
+                .iter()
+                .filter_map(|i| translate(i.start, i.end))
+                .collect(),
+            utr5_inferred: utr5.is_some() && transcript.utr5.as_ref().is_some_and(|u| u.inferred),
+            utr3_inferred: utr3.is_some() && transcript.utr3.as_ref().is_some_and(|u| u.inferred),
+            utr5,
+            utr3,
+            isoform: transcript.isoform.clone(),
+        };
+        for (utr_start, utr_end) in model.utr5.iter().chain(model.utr3.iter()) {
+            model.range.0 = model.range.0.min(*utr_start);
+            model.range.1 = model.range.1.max(*utr_end);
+        }
+        Some((model, reports))
+    }
+}
+
+/// Feature models with the refinement reports of their UTRs.
+pub type ReportedModels = Vec<(FeatureModel, Vec<UtrReport>)>;
+
+/// Lays out every transcript of `genes` that is part of the input, together
+/// with the refinement report of its UTRs. Also returns what was skipped.
+pub fn build_models(
+    genes: &[Gene],
+    translator: &Translator,
+    strategy: &dyn UtrBoundaryStrategy,
+    profile: &[f32],
+) -> (ReportedModels, Vec<Diagnostic>) {
+    let per_gene: Vec<(ReportedModels, Vec<Diagnostic>)> = genes
+        .par_iter()
+        .map(|gene| {
+            let mut diagnostics = Vec::new();
+            let models = gene
+                .transcripts
+                .iter()
+                .filter_map(|transcript| {
+                    FeatureModel::from_transcript(
+                        gene,
+                        transcript,
+                        translator,
+                        strategy,
+                        profile,
+                        &mut diagnostics,
+                    )
+                })
+                .collect();
+            (models, diagnostics)
+        })
+        .collect();
+
+    let mut models = Vec::new();
+    let mut diagnostics = Vec::new();
+    for (gene_models, gene_diagnostics) in per_gene {
+        models.extend(gene_models);
+        diagnostics.extend(gene_diagnostics);
+    }
+    (models, diagnostics)
+}
+
+/// Finds the intergenic intervals between `models` on every chromosome of
+/// the input, including chromosomes without any model, and the promoter
+/// windows of `promoter_length` nucleotides upstream of each transcript start.
+pub fn find_regions(
+    models: &[FeatureModel],
+    translator: &Translator,
+    promoter_length: usize,
+) -> Regions {
+    let mut by_chromosome: BTreeMap<&YeastChromosome, Vec<&FeatureModel>> = BTreeMap::new();
+    for model in models {
+        by_chromosome
+            .entry(&model.chromosome)
+            .or_default()
+            .push(model);
+    }
+
+    let mut regions = Regions::new();
+    for (chromosome, first, length) in translator.chromosomes() {
+        if length == 0 {
+            continue;
+        }
+        let mut models = by_chromosome.remove(&chromosome).unwrap_or_default();
+        let last = first + length - 1;
+        models.sort_by_key(|model| model.range);
+
+        let mut next_free = first;
+        let mut previous: Option<(&str, usize, Strand)> = None;
+        for model in &models {
+            let (start, end) = model.range;
+            if start > next_free {
+                let next = Some((model.systematic_name.as_str(), start, model.strand));
+                regions.add_gap(next_free, start - 1, previous, next);
+            }
+            if end + 1 > next_free {
+                next_free = end + 1;
+                previous = Some((model.systematic_name.as_str(), end, model.strand));
+            }
+        }
+        if next_free <= last {
+            regions.add_gap(next_free, last, previous, None);
+        }
+
+        if promoter_length == 0 {
+            continue;
+        }
+        for model in &models {
+            let (start, end) = model.range;
+            let window = match model.strand {
+                Strand::Plus if start > first => {
+                    Some((start.saturating_sub(promoter_length).max(first), start - 1))
+                }
+                Strand::Minus if end < last => Some((end + 1, (end + promoter_length).min(last))),
+                _ => None,
+            };
+            if let Some((start, end)) = window {
+                regions.add_promoter(start, end, &model.systematic_name);
+            }
+        }
+    }
+    regions
+}
+
+/// Turns an SGD feature type, as found in FASTA headers (`Verified ORF`,
+/// `tRNA_gene`) or GFF3 files (`long_terminal_repeat`), into a category and an
+/// optional ORF qualifier.
+pub fn feature_category(kind: &str) -> (String, Option<String>) {
+    if let Some(qualifier) = kind.strip_suffix(" ORF") {
+        return ("ORF".to_string(), Some(qualifier.to_string()));
+    }
+
+    let category = match kind {
+        "gene" | "ORF" => "ORF".to_string(),
+        "long_terminal_repeat" => "LTR".to_string(),
+        "LTR_retrotransposon" => "Ty element".to_string(),
+        "Y_prime_element" => "Y' element".to_string(),
+        _ => kind
+            .strip_suffix("RNA_gene")
+            .map(|prefix| format!("{}RNA", prefix))
+            .unwrap_or_else(|| kind.to_string())
+            .replace('_', " "),
+    };
+    (category, None)
+}
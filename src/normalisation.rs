@@ -0,0 +1,232 @@
+use std::{fmt::Display, str::FromStr};
+
+use rayon::prelude::*;
+
+use crate::{
+    model::FeatureModel,
+    stats::{mean, quantile, sorted},
+    translator::Translator,
+};
+
+/// How raw reactivities are scaled to a common range.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Method {
+    /// Divide by the mean of the top 2–10% of values, ignoring the top 2%.
+    TwoEight,
+    /// Divide by the mean of the top 10% of values after excluding outliers
+    /// above Q3 + 1.5 × IQR.
+    BoxPlot,
+    /// Divide by the 90th percentile.
+    Percentile90,
+    /// Clamp values to the 5th–95th percentile, then divide by the 95th.
+    Winsorise,
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "2-8" => Ok(Self::TwoEight),
+            "boxplot" => Ok(Self::BoxPlot),
+            "90th" => Ok(Self::Percentile90),
+            "winsorise" => Ok(Self::Winsorise),
+            _ => Err(format!("Invalid normalisation method: {}", s)),
+        }
+    }
+}
+
+impl Display for Method {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TwoEight => write!(f, "2-8%"),
+            Self::BoxPlot => write!(f, "box-plot"),
+            Self::Percentile90 => write!(f, "90th percentile"),
+            Self::Winsorise => write!(f, "winsorised"),
+        }
+    }
+}
+
+/// Set of positions a normalisation factor is computed over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scope {
+    Genome,
+    Chromosome,
+    /// The exonic blocks of every feature model. Positions outside of
+    /// features are left empty; positions shared by several models take the
+    /// value of the first one.
+    Transcript,
+}
+
+impl FromStr for Scope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "genome" => Ok(Self::Genome),
+            "chromosome" => Ok(Self::Chromosome),
+            "transcript" => Ok(Self::Transcript),
+            _ => Err(format!("Invalid normalisation scope: {}", s)),
+        }
+    }
+}
+
+impl Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Genome => write!(f, "genome"),
+            Self::Chromosome => write!(f, "chromosome"),
+            Self::Transcript => write!(f, "transcript"),
+        }
+    }
+}
+
+impl Method {
+    /// Normalises `values`, ignoring NaN. All values become NaN when no
+    /// positive factor can be found.
+    pub fn apply(&self, values: &[f32]) -> Vec<f32> {
+        let sorted = sorted(values);
+        let n = sorted.len();
+        // Number of values in the top `fraction`, at least one.
+        let top = |n: usize, fraction: f32| ((n as f32 * fraction).round() as usize).max(1);
+
+        let (factor, bounds) = match self {
+            Self::TwoEight => {
+                let skip = (n as f32 * 0.02).round() as usize;
+                let end = n.saturating_sub(skip);
+                let start = end.saturating_sub(top(n, 0.08));
+                (mean(&sorted[start..end]), None)
+            }
+            Self::BoxPlot => {
+                let (q1, q3) = (quantile(&sorted, 0.25), quantile(&sorted, 0.75));
+                let limit = q3 + 1.5 * (q3 - q1);
+                let kept = sorted.partition_point(|x| *x <= limit);
+                let start = kept.saturating_sub(top(kept, 0.1));
+                (mean(&sorted[start..kept]), None)
+            }
+            Self::Percentile90 => (quantile(&sorted, 0.9), None),
+            Self::Winsorise => {
+                let (low, high) = (quantile(&sorted, 0.05), quantile(&sorted, 0.95));
+                (high, Some((low, high)))
+            }
+        };
+
+        if factor.is_nan() || factor <= 0.0 {
+            return vec![f32::NAN; values.len()];
+        }
+        values
+            .iter()
+            .map(|x| match bounds {
+                Some((low, high)) => x.clamp(low, high) / factor,
+                None => x / factor,
+            })
+            .collect()
+    }
+}
+
+/// A normalisation method applied over a scope, given as `METHOD[:SCOPE]`,
+/// e.g. `2-8:chromosome`. The scope defaults to the genome.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Normalisation {
+    pub method: Method,
+    pub scope: Scope,
+}
+
+impl FromStr for Normalisation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (method, scope) = match s.split_once(':') {
+            Some((method, scope)) => (method.parse()?, scope.parse()?),
+            None => (s.parse()?, Scope::Genome),
+        };
+        Ok(Self { method, scope })
+    }
+}
+
+impl Normalisation {
+    pub fn column_name(&self) -> String {
+        format!("Normalised {} ({})", self.method, self.scope)
+    }
+
+    /// Input indices of every group normalised on its own.
+    fn groups(
+        &self,
+        profile: &[f32],
+        translator: &Translator,
+        models: &[FeatureModel],
+    ) -> Vec<Vec<usize>> {
+        match self.scope {
+            Scope::Genome => vec![(0..profile.len()).collect()],
+            Scope::Chromosome => translator
+                .chromosomes()
+                .into_iter()
+                .map(|(_, offset, length)| (offset..(offset + length).min(profile.len())).collect())
+                .collect(),
+            Scope::Transcript => models
+                .iter()
+                .map(|model| {
+                    model
+                        .blocks()
+                        .into_iter()
+                        .flat_map(|(start, end)| start..=end)
+                        .filter(|i| *i < profile.len())
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// Normalised copy of `profile`. With `bases`, e.g. `AC` for DMS, each
+    /// listed base of `sequence` is normalised separately and all other
+    /// positions are left empty.
+    pub fn apply(
+        &self,
+        profile: &[f32],
+        sequence: &str,
+        bases: Option<&str>,
+        translator: &Translator,
+        models: &[FeatureModel],
+    ) -> Vec<f32> {
+        let sequence = sequence.as_bytes();
+        let groups: Vec<Vec<usize>> = self
+            .groups(profile, translator, models)
+            .into_iter()
+            .flat_map(|group| match bases {
+                None => vec![group],
+                Some(bases) => bases
+                    .to_ascii_uppercase()
+                    .replace('T', "U")
+                    .bytes()
+                    .map(|base| {
+                        group
+                            .iter()
+                            .copied()
+                            .filter(|i| sequence.get(*i) == Some(&base))
+                            .collect()
+                    })
+                    .collect(),
+            })
+            .collect();
+
+        let normalised: Vec<Vec<f32>> = groups
+            .par_iter()
+            .map(|group| {
+                let values: Vec<f32> = group.iter().map(|i| profile[*i]).collect();
+                self.method.apply(&values)
+            })
+            .collect();
+
+        let mut result = vec![f32::NAN; profile.len()];
+        let mut assigned = vec![false; profile.len()];
+        for (group, values) in groups.iter().zip(normalised) {
+            for (i, value) in group.iter().zip(values) {
+                if !assigned[*i] {
+                    result[*i] = value;
+                    assigned[*i] = true;
+                }
+            }
+        }
+        result
+    }
+}
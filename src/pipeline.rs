@@ -0,0 +1,392 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    str::FromStr,
+};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use log::debug;
+use rayon::prelude::*;
+
+use crate::{
+    annotation::{Annotations, Label, Subtype, TranscriptStructure},
+    chemistry::ProbeArgs,
+    data::ensure_all_data,
+    diagnostics::Diagnostic,
+    gene::{genes_from_gff, infer_missing_utrs, load_genes_from_fasta, Gene, UtrFallback},
+    gff::load_gff,
+    isoform::{IsoformSelection, UtrCatalog},
+    model::{build_models, FeatureModel, ReportedModels},
+    translator::{Translator, YeastChromosome},
+    utr::{CoverageThreshold, NanRun, NoTrimming, SignalDropOff, UtrBoundaryStrategy},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeatureSource {
+    Gff,
+    Fasta,
+}
+
+impl FromStr for FeatureSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gff" => Ok(Self::Gff),
+            "fasta" => Ok(Self::Fasta),
+            _ => Err(format!("Invalid feature source: {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UtrStrategy {
+    None,
+    NanRun,
+    Coverage,
+    DropOff,
+}
+
+impl FromStr for UtrStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "nan-run" => Ok(Self::NanRun),
+            "coverage" => Ok(Self::Coverage),
+            "drop-off" => Ok(Self::DropOff),
+            _ => Err(format!("Invalid UTR strategy: {}", s)),
+        }
+    }
+}
+
+/// Command line options shared by every tool that lays out feature models on
+/// an input profile.
+#[derive(Args, Debug, Clone)]
+pub struct ModelArgs {
+    /// Where feature coordinates come from: `gff` or `fasta` (SGD FASTA headers)
+    #[arg(long, default_value = "gff")]
+    pub source: FeatureSource,
+
+    /// GFF3 file used with `--source gff`
+    #[arg(long, default_value = "data/saccharomyces_cerevisiae.gff.gz")]
+    pub gff: String,
+
+    /// UTR FASTA files in the format of SGD's all-ORF UTRs, as `SOURCE=PATH`
+    #[arg(
+        long,
+        default_values = ["SGD=data/5prime_utr.fsa.gz", "SGD=data/3prime_utr.fsa.gz"]
+    )]
+    pub utr_fasta: Vec<String>,
+
+    /// Transcript isoform tables (chromosome, strand, start, end, systematic
+    /// name, support), e.g. from TIF-seq, as `SOURCE=PATH`
+    #[arg(long)]
+    pub isoforms: Vec<String>,
+
+    /// Which isoforms of a gene are annotated: `major`, `longest` or `all`
+    #[arg(long, default_value = "longest")]
+    pub isoform_selection: IsoformSelection,
+
+    /// How UTRs are guessed for ORFs without any: `none`, `fixed`, `median`
+    /// or `neighbour`
+    #[arg(long, default_value = "none")]
+    pub utr_fallback: UtrFallback,
+
+    /// Length of fixed and neighbour-capped fallback UTRs
+    #[arg(long, default_value_t = 100)]
+    pub utr_fallback_length: usize,
+
+    /// How UTR ends are refined: `none`, `nan-run`, `coverage` or `drop-off`
+    #[arg(long, default_value = "nan-run")]
+    pub utr_strategy: UtrStrategy,
+
+    /// Window size used by the UTR refinement strategies, at least 1
+    #[arg(
+        long,
+        default_value_t = 6,
+        value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..)
+    )]
+    pub utr_window: usize,
+
+    /// Minimal covered fraction (`coverage`, default 0.5) or signal ratio
+    /// (`drop-off`, default 0.2) at which a UTR is cut
+    #[arg(long)]
+    pub utr_threshold: Option<f32>,
+}
+
+impl ModelArgs {
+    pub fn strategy(&self) -> Box<dyn UtrBoundaryStrategy> {
+        match self.utr_strategy {
+            UtrStrategy::None => Box::new(NoTrimming),
+            UtrStrategy::NanRun => Box::new(NanRun {
+                window: self.utr_window,
+            }),
+            UtrStrategy::Coverage => Box::new(CoverageThreshold {
+                window: self.utr_window,
+                min_fraction: self.utr_threshold.unwrap_or(0.5),
+            }),
+            UtrStrategy::DropOff => Box::new(SignalDropOff {
+                window: self.utr_window,
+                ratio: self.utr_threshold.unwrap_or(0.2),
+            }),
+        }
+    }
+
+    /// Loads the UTR and isoform datasets.
+    pub fn catalog(&self) -> Result<UtrCatalog> {
+        let mut catalog = UtrCatalog::new();
+        for argument in &self.utr_fasta {
+            let (source, path) = source_and_path(argument);
+            catalog.add_utr_fasta(Path::new(path), source);
+        }
+        for argument in &self.isoforms {
+            let (source, path) = source_and_path(argument);
+            catalog.add_isoform_table(Path::new(path), source)?;
+        }
+        Ok(catalog)
+    }
+
+    /// Loads the genes with their selected isoforms and fallback UTRs, and
+    /// reports the genes and isoforms that were dropped.
+    pub fn genes(&self, translator: &Translator) -> Result<(Vec<Gene>, Vec<Diagnostic>)> {
+        let catalog = self.catalog()?;
+        let (genes, mut diagnostics) = match self.source {
+            FeatureSource::Gff => genes_from_gff(&load_gff(Path::new(&self.gff))?),
+            FeatureSource::Fasta => load_genes_from_fasta(Path::new("data")),
+        };
+        let (mut genes, dropped): (Vec<Gene>, Vec<Vec<Diagnostic>>) = genes
+            .into_par_iter()
+            .map(|gene| gene.with_isoforms(&catalog, self.isoform_selection))
+            .unzip();
+        diagnostics.extend(dropped.into_iter().flatten());
+        let lengths: HashMap<YeastChromosome, usize> = translator
+            .chromosomes()
+            .into_iter()
+            .map(|(chromosome, _, length)| (chromosome, length))
+            .collect();
+        infer_missing_utrs(
+            &mut genes,
+            self.utr_fallback,
+            self.utr_fallback_length,
+            &lengths,
+        );
+        Ok((genes, diagnostics))
+    }
+
+    /// Lays out all transcripts on the input, refining UTRs on `profile`.
+    /// Also returns every feature that was skipped or altered on the way.
+    pub fn build(
+        &self,
+        translator: &Translator,
+        profile: &[f32],
+    ) -> Result<(ReportedModels, Vec<Diagnostic>)> {
+        let (genes, mut diagnostics) = self.genes(translator)?;
+        let strategy = self.strategy();
+        let (models, skipped) = build_models(&genes, translator, strategy.as_ref(), profile);
+        diagnostics.extend(skipped);
+        Ok((models, diagnostics))
+    }
+}
+
+/// Command line options of every tool that works on feature models: how the
+/// models are built, the probing experiment and the number of threads.
+#[derive(Args, Debug, Clone)]
+pub struct CommonArgs {
+    #[command(flatten)]
+    pub models: ModelArgs,
+
+    #[command(flatten)]
+    pub probe: ProbeArgs,
+
+    /// Number of worker threads (defaults to the number of logical CPUs)
+    #[arg(short, long)]
+    pub threads: Option<usize>,
+}
+
+impl CommonArgs {
+    /// Sizes the global thread pool and downloads missing data files.
+    pub fn setup(&self) -> Result<()> {
+        if let Some(threads) = self.threads {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build_global()?;
+        }
+        ensure_all_data()
+    }
+
+    /// The translator of `genome` and the feature models laid out on
+    /// `profile`, without their UTR reports and diagnostics.
+    pub fn feature_models(
+        &self,
+        genome: &str,
+        profile: &[f32],
+    ) -> Result<(Translator, Vec<FeatureModel>)> {
+        let translator = Translator::new(genome);
+        let (models, _) = self.models.build(&translator, profile)?;
+        let models = models.into_iter().map(|(model, _)| model).collect();
+        Ok((translator, models))
+    }
+}
+
+/// Splits a `SOURCE=PATH` argument. Without a source, the file name is used.
+pub fn source_and_path(argument: &str) -> (&str, &str) {
+    argument.split_once('=').unwrap_or_else(|| {
+        let name = Path::new(argument)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(argument);
+        (name, argument)
+    })
+}
+
+fn create_graph(models: &[&FeatureModel]) -> HashMap<String, Vec<String>> {
+    let names: Vec<String> = models.iter().map(|model| model.id()).collect();
+    let ranges: HashMap<&String, (usize, usize)> = names
+        .iter()
+        .zip(models.iter())
+        .map(|(name, model)| (name, model.range))
+        .collect();
+    let mut graph = HashMap::new();
+
+    for i in 0..names.len() {
+        let name_i = names.get(i).unwrap().to_string();
+
+        if let Some((start_i, end_i)) = ranges.get(&name_i) {
+            for j in (i + 1)..names.len() {
+                let name_j = names.get(j).unwrap().to_string();
+
+                if let Some((start_j, end_j)) = ranges.get(&name_j) {
+                    if (start_i < start_j && start_j < end_i)
+                        || (start_i < end_j && end_j < end_i)
+                        || (start_j < start_i && start_i < end_j)
+                        || (start_j < end_i && end_i < end_j)
+                    {
+                        if !graph.contains_key(&name_i) {
+                            graph.insert(name_i.clone(), Vec::new());
+                        }
+                        graph.get_mut(&name_i).unwrap().push(name_j.clone());
+
+                        if !graph.contains_key(&name_j) {
+                            graph.insert(name_j.clone(), Vec::new());
+                        }
+                        graph.get_mut(&name_j).unwrap().push(name_i.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    graph
+}
+
+fn determine_order(
+    models: &[&FeatureModel],
+    graph: &HashMap<String, Vec<String>>,
+) -> HashMap<String, usize> {
+    let mut colors: HashMap<String, usize> = HashMap::new();
+
+    for model in models.iter() {
+        let current = &model.id();
+        let adjacent = graph.get(current).map(|a| a.as_slice()).unwrap_or_default();
+        // With n neighbours one of the first n + 1 colours is always free.
+        let mut available = vec![true; adjacent.len() + 1];
+        for next in adjacent {
+            if let Some(slot) = colors.get(next).and_then(|color| available.get_mut(*color)) {
+                *slot = false;
+            }
+        }
+
+        if let Some(color) = available.iter().position(|a| *a) {
+            colors.insert(current.to_string(), color);
+        }
+    }
+
+    colors
+}
+
+fn fill_annotations(
+    models: &[&FeatureModel],
+    orders: &HashMap<String, usize>,
+) -> Result<Annotations> {
+    let max = orders.values().copied().max().unwrap_or(0);
+    let mut annotations = Annotations::new(max + 1);
+
+    for model in models.iter() {
+        let order = orders
+            .get(&model.id())
+            .with_context(|| format!("Feature {} was not assigned a track", model.id()))?;
+        let (start, end) = model.range;
+        let track = annotations.track_mut(*order);
+        track.add_feature(
+            start,
+            end,
+            Label {
+                category: model.category.clone(),
+                qualifier: model.qualifier.clone().unwrap_or_default(),
+                isoform: model.isoform.clone().unwrap_or_default(),
+                systematic_name: model.systematic_name.clone(),
+                standard_name: model.standard_name.clone(),
+                strand: model.strand,
+            },
+        );
+        track.set_structure(TranscriptStructure::new(
+            model.strand,
+            model.blocks(),
+            model.cds_range(),
+        ));
+        track.set_subtype(start, end, Subtype::Unknown);
+
+        if let Some((start, end)) = model.utr5 {
+            let subtype = if model.utr5_inferred {
+                Subtype::InferredUtr5
+            } else {
+                Subtype::Utr5
+            };
+            track.set_subtype(start, end, subtype);
+        }
+        if let Some((start, end)) = model.utr3 {
+            let subtype = if model.utr3_inferred {
+                Subtype::InferredUtr3
+            } else {
+                Subtype::Utr3
+            };
+            track.set_subtype(start, end, subtype);
+        }
+        for (start, end) in model.exons.iter() {
+            track.set_subtype(*start, *end, Subtype::Exon);
+        }
+        for (start, end) in model.introns.iter() {
+            track.set_subtype(*start, *end, Subtype::Intron);
+        }
+    }
+
+    Ok(annotations)
+}
+
+/// Lays out `models` on as few tracks as possible and paints their labels,
+/// exon structures and subtypes.
+pub fn annotate(models: &[FeatureModel]) -> Result<Annotations> {
+    // Features never overlap across chromosomes, so layout and annotation can
+    // run independently for each of them.
+    let mut by_chromosome: BTreeMap<YeastChromosome, Vec<&FeatureModel>> = BTreeMap::new();
+    for model in models.iter() {
+        by_chromosome
+            .entry(model.chromosome.clone())
+            .or_default()
+            .push(model);
+    }
+
+    let parts: Vec<Annotations> = by_chromosome
+        .par_iter()
+        .map(|(chromosome, features)| {
+            debug!("Annotating chromosome {}", chromosome);
+            let graph = create_graph(features);
+            let orders = determine_order(features, &graph);
+            fill_annotations(features, &orders)
+        })
+        .collect::<Result<_>>()?;
+    Ok(Annotations::merge(parts))
+}
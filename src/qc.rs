@@ -0,0 +1,341 @@
+use std::{collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    model::FeatureModel,
+    stats::{self, quantile},
+    translator::Translator,
+};
+
+/// Number of outliers and NaN runs listed individually in the report.
+const LISTED: usize = 20;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChromosomeQc {
+    pub chromosome: String,
+    pub offset: usize,
+    pub length: usize,
+    pub nan_fraction: f32,
+    pub long_nan_runs: usize,
+}
+
+/// Missing values over the exonic blocks of all features of one category.
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureClassQc {
+    pub category: String,
+    pub features: usize,
+    pub positions: usize,
+    pub nan_fraction: f32,
+}
+
+/// Statistics of the values of the profile. Those that cannot be computed
+/// from too few values are `None`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Distribution {
+    pub values: usize,
+    pub min: Option<f32>,
+    pub max: Option<f32>,
+    pub mean: Option<f32>,
+    /// Sample standard deviation, from at least two values.
+    pub sd: Option<f32>,
+    pub negative_fraction: f32,
+    /// Quantiles at 1, 5, 25, 50, 75, 95 and 99%, empty without values.
+    pub quantiles: BTreeMap<String, f32>,
+}
+
+/// A position of the input, named by chromosome and 1-based position when it
+/// is mapped.
+#[derive(Debug, Clone, Serialize)]
+pub struct Site {
+    pub index: usize,
+    pub chromosome: Option<String>,
+    pub position: Option<usize>,
+}
+
+impl Site {
+    fn new(index: usize, translator: &Translator) -> Self {
+        let location = translator.locate(index);
+        Self {
+            index,
+            chromosome: location.as_ref().map(|(c, _)| format!("chr{}", c)),
+            position: location.map(|(_, p)| p),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Outlier {
+    #[serde(flatten)]
+    pub site: Site,
+    pub value: f32,
+}
+
+/// Values above Q3 + 1.5 × IQR, the highest ones listed.
+#[derive(Debug, Clone, Serialize)]
+pub struct Outliers {
+    pub fence: Option<f32>,
+    pub count: usize,
+    pub fraction: f32,
+    pub highest: Vec<Outlier>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NanRun {
+    #[serde(flatten)]
+    pub start: Site,
+    pub length: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QcReport {
+    pub positions: usize,
+    pub nan_fraction: f32,
+    /// Input positions outside of every mapped chromosome.
+    pub unassigned_positions: usize,
+    pub unmapped_chromosomes: Vec<String>,
+    pub chromosomes: Vec<ChromosomeQc>,
+    pub feature_classes: Vec<FeatureClassQc>,
+    pub distribution: Distribution,
+    pub outliers: Outliers,
+    /// Minimal length of a reported NaN run.
+    pub min_nan_run: usize,
+    pub long_nan_runs: usize,
+    pub longest_nan_runs: Vec<NanRun>,
+}
+
+fn nan_fraction(values: impl Iterator<Item = f32>) -> f32 {
+    let (mut total, mut nan) = (0, 0);
+    for value in values {
+        total += 1;
+        if value.is_nan() {
+            nan += 1;
+        }
+    }
+    nan as f32 / total as f32
+}
+
+/// Start and length of every run of at least `min_length` NaN values.
+fn nan_runs(profile: &[f32], min_length: usize) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, value) in profile.iter().chain([0.0].iter()).enumerate() {
+        match (value.is_nan(), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                if i - s >= min_length.max(1) {
+                    runs.push((s, i - s));
+                }
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    runs
+}
+
+/// Checks `profile` before annotation.
+pub fn qc_report(
+    profile: &[f32],
+    translator: &Translator,
+    models: &[FeatureModel],
+    min_nan_run: usize,
+) -> QcReport {
+    let runs = nan_runs(profile, min_nan_run);
+
+    let mapped = translator.chromosomes();
+    let chromosomes: Vec<ChromosomeQc> = mapped
+        .iter()
+        .map(|(chromosome, offset, length)| {
+            let end = (offset + length).min(profile.len());
+            ChromosomeQc {
+                chromosome: format!("chr{}", chromosome),
+                offset: *offset,
+                length: *length,
+                nan_fraction: nan_fraction(profile[(*offset).min(end)..end].iter().copied()),
+                long_nan_runs: runs
+                    .iter()
+                    .filter(|(start, _)| offset <= start && *start < end)
+                    .count(),
+            }
+        })
+        .collect();
+    let assigned: usize = mapped.iter().map(|(_, _, length)| length).sum();
+
+    let mut classes: BTreeMap<&str, (usize, usize, usize)> = BTreeMap::new();
+    for model in models {
+        let entry = classes.entry(&model.category).or_default();
+        entry.0 += 1;
+        for (start, end) in model.blocks() {
+            for i in start..=end {
+                entry.1 += 1;
+                if profile.get(i).is_none_or(|x| x.is_nan()) {
+                    entry.2 += 1;
+                }
+            }
+        }
+    }
+    let feature_classes = classes
+        .into_iter()
+        .map(|(category, (features, positions, nan))| FeatureClassQc {
+            category: category.to_string(),
+            features,
+            positions,
+            nan_fraction: nan as f32 / positions as f32,
+        })
+        .collect();
+
+    let sorted = stats::sorted(profile);
+    let n = sorted.len();
+    if n < 2 {
+        warn!(
+            "Only {} value(s) in the profile, leaving statistics of the QC report empty",
+            n
+        );
+    }
+    let fraction = |count: usize| if n == 0 { 0.0 } else { count as f32 / n as f32 };
+    let mean = (n > 0).then(|| stats::mean(&sorted));
+    let sd = stats::sd(&sorted);
+    let quantiles = if n == 0 {
+        BTreeMap::new()
+    } else {
+        [1, 5, 25, 50, 75, 95, 99]
+            .iter()
+            .map(|p| (format!("p{:02}", p), quantile(&sorted, *p as f32 / 100.0)))
+            .collect()
+    };
+    let distribution = Distribution {
+        values: n,
+        min: sorted.first().copied(),
+        max: sorted.last().copied(),
+        mean,
+        sd,
+        negative_fraction: fraction(sorted.iter().filter(|x| **x < 0.0).count()),
+        quantiles,
+    };
+
+    let fence = (n > 0).then(|| {
+        let (q1, q3) = (quantile(&sorted, 0.25), quantile(&sorted, 0.75));
+        q3 + 1.5 * (q3 - q1)
+    });
+    let limit = fence.unwrap_or(f32::INFINITY);
+    let count = n - sorted.partition_point(|x| *x <= limit);
+    let mut highest: Vec<(usize, f32)> = profile
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(_, x)| *x > limit)
+        .collect();
+    highest.sort_by(|a, b| b.1.total_cmp(&a.1));
+    let outliers = Outliers {
+        fence,
+        count,
+        fraction: fraction(count),
+        highest: highest
+            .into_iter()
+            .take(LISTED)
+            .map(|(i, value)| Outlier {
+                site: Site::new(i, translator),
+                value,
+            })
+            .collect(),
+    };
+
+    let mut longest = runs.clone();
+    longest.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    QcReport {
+        positions: profile.len(),
+        nan_fraction: nan_fraction(profile.iter().copied()),
+        unassigned_positions: profile.len().saturating_sub(assigned),
+        unmapped_chromosomes: translator
+            .unmapped()
+            .iter()
+            .map(|c| format!("chr{}", c))
+            .collect(),
+        chromosomes,
+        feature_classes,
+        distribution,
+        outliers,
+        min_nan_run,
+        long_nan_runs: runs.len(),
+        longest_nan_runs: longest
+            .into_iter()
+            .take(LISTED)
+            .map(|(start, length)| NanRun {
+                start: Site::new(start, translator),
+                length,
+            })
+            .collect(),
+    }
+}
+
+pub fn write_qc_json<W: Write>(writer: &mut W, report: &QcReport) -> Result<()> {
+    serde_json::to_writer_pretty(&mut *writer, report)?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Writes a short human-readable version of `report`.
+pub fn write_qc_summary<W: Write>(writer: &mut W, report: &QcReport) -> Result<()> {
+    let percent = |x: f32| format!("{:.1}%", x * 100.0);
+    writeln!(
+        writer,
+        "{} positions, {} NaN, {} outside of mapped chromosomes",
+        report.positions,
+        percent(report.nan_fraction),
+        report.unassigned_positions
+    )?;
+    if !report.unmapped_chromosomes.is_empty() {
+        writeln!(
+            writer,
+            "Unmapped chromosomes: {}",
+            report.unmapped_chromosomes.join(", ")
+        )?;
+    }
+    for chromosome in &report.chromosomes {
+        writeln!(
+            writer,
+            "  {:<8} {:>9} nt  {:>6} NaN  {} NaN runs",
+            chromosome.chromosome,
+            chromosome.length,
+            percent(chromosome.nan_fraction),
+            chromosome.long_nan_runs
+        )?;
+    }
+    for class in &report.feature_classes {
+        writeln!(
+            writer,
+            "  {:<24} {:>5} features  {:>6} NaN",
+            class.category,
+            class.features,
+            percent(class.nan_fraction)
+        )?;
+    }
+    let d = &report.distribution;
+    let statistic = |x: Option<f32>| x.map_or_else(|| "n/a".to_string(), |x| format!("{:.3}", x));
+    writeln!(
+        writer,
+        "Values: mean {}, sd {}, median {}, range {} to {}",
+        statistic(d.mean),
+        statistic(d.sd),
+        statistic(d.quantiles.get("p50").copied()),
+        statistic(d.min),
+        statistic(d.max)
+    )?;
+    writeln!(
+        writer,
+        "Outliers above {}: {} ({})",
+        statistic(report.outliers.fence),
+        report.outliers.count,
+        percent(report.outliers.fraction)
+    )?;
+    writeln!(
+        writer,
+        "NaN runs of at least {} nt: {}",
+        report.min_nan_run, report.long_nan_runs
+    )?;
+    Ok(())
+}
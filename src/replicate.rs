@@ -0,0 +1,211 @@
+use std::io::Write;
+
+use anyhow::{bail, Result};
+use rayon::prelude::*;
+
+use crate::{
+    model::FeatureModel,
+    stats::{mean, number, sd},
+    translator::Translator,
+};
+
+/// Fails unless `other`, the sequence of replicate `name`, equals
+/// `reference`.
+pub fn check_sequence(reference: &str, other: &str, name: &str) -> Result<()> {
+    if reference.len() != other.len() {
+        bail!(
+            "Replicate {} has {} positions instead of {}",
+            name,
+            other.len(),
+            reference.len()
+        );
+    }
+    if let Some(i) = reference
+        .bytes()
+        .zip(other.bytes())
+        .position(|(a, b)| a != b)
+    {
+        bail!(
+            "Replicate {} differs from the input at line {}",
+            name,
+            i + 2
+        );
+    }
+    Ok(())
+}
+
+/// Per-position statistics of several replicate profiles, ignoring NaN.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedProfile {
+    pub mean: Vec<f32>,
+    /// Sample standard deviation, NaN with fewer than two values.
+    pub sd: Vec<f32>,
+    /// Number of replicates with a value.
+    pub count: Vec<usize>,
+}
+
+pub fn merge(profiles: &[Vec<f32>]) -> MergedProfile {
+    let length = profiles.iter().map(|p| p.len()).max().unwrap_or(0);
+    let stats: Vec<(f32, f32, usize)> = (0..length)
+        .into_par_iter()
+        .map(|i| {
+            let values: Vec<f32> = profiles
+                .iter()
+                .filter_map(|p| p.get(i).copied())
+                .filter(|x| !x.is_nan())
+                .collect();
+            (mean(&values), sd(&values).unwrap_or(f32::NAN), values.len())
+        })
+        .collect();
+
+    MergedProfile {
+        mean: stats.iter().map(|s| s.0).collect(),
+        sd: stats.iter().map(|s| s.1).collect(),
+        count: stats.iter().map(|s| s.2).collect(),
+    }
+}
+
+/// Pearson correlation of paired values.
+pub fn pearson(x: &[f32], y: &[f32]) -> f32 {
+    let n = x.len() as f64;
+    if x.len() < 2 {
+        return f32::NAN;
+    }
+    let mean_x = x.iter().map(|v| *v as f64).sum::<f64>() / n;
+    let mean_y = y.iter().map(|v| *v as f64).sum::<f64>() / n;
+    let (mut cov, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (a, b) in x.iter().zip(y) {
+        let (dx, dy) = (*a as f64 - mean_x, *b as f64 - mean_y);
+        cov += dx * dy;
+        var_x += dx * dx;
+        var_y += dy * dy;
+    }
+    if var_x == 0.0 || var_y == 0.0 {
+        return f32::NAN;
+    }
+    (cov / (var_x * var_y).sqrt()) as f32
+}
+
+/// 1-based ranks of `values`, ties sharing their average rank.
+fn ranks(values: &[f32]) -> Vec<f32> {
+    let mut order: Vec<usize> = (0..values.len()).collect();
+    order.sort_by(|a, b| values[*a].total_cmp(&values[*b]));
+    let mut ranks = vec![0.0; values.len()];
+    let mut i = 0;
+    while i < order.len() {
+        let mut j = i;
+        while j + 1 < order.len() && values[order[j + 1]] == values[order[i]] {
+            j += 1;
+        }
+        let rank = (i + j) as f32 / 2.0 + 1.0;
+        for k in &order[i..=j] {
+            ranks[*k] = rank;
+        }
+        i = j + 1;
+    }
+    ranks
+}
+
+/// Spearman correlation of paired values.
+pub fn spearman(x: &[f32], y: &[f32]) -> f32 {
+    pearson(&ranks(x), &ranks(y))
+}
+
+/// Agreement of two replicates over a chromosome or a transcript.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicateCorrelation {
+    /// `chromosome` or `transcript`.
+    pub scope: &'static str,
+    pub name: String,
+    pub replicate_a: String,
+    pub replicate_b: String,
+    /// Number of positions with a value in both replicates.
+    pub positions: usize,
+    pub pearson: f32,
+    pub spearman: f32,
+}
+
+/// Correlates every pair of `profiles` over each chromosome and each
+/// transcript of `models`.
+pub fn correlations(
+    names: &[String],
+    profiles: &[Vec<f32>],
+    translator: &Translator,
+    models: &[FeatureModel],
+) -> Vec<ReplicateCorrelation> {
+    let mut groups: Vec<(&'static str, String, Vec<usize>)> = translator
+        .chromosomes()
+        .into_iter()
+        .map(|(chromosome, offset, length)| {
+            (
+                "chromosome",
+                format!("chr{}", chromosome),
+                (offset..offset + length).collect(),
+            )
+        })
+        .collect();
+    groups.extend(models.iter().map(|model| {
+        (
+            "transcript",
+            model.transcript_id(),
+            model
+                .blocks()
+                .into_iter()
+                .flat_map(|(start, end)| start..=end)
+                .collect(),
+        )
+    }));
+
+    let mut pairs = Vec::new();
+    for a in 0..profiles.len() {
+        for b in (a + 1)..profiles.len() {
+            pairs.push((a, b));
+        }
+    }
+
+    groups
+        .par_iter()
+        .flat_map_iter(|(scope, name, indices)| {
+            pairs.iter().map(move |(a, b)| {
+                let (x, y): (Vec<f32>, Vec<f32>) = indices
+                    .iter()
+                    .filter_map(|i| Some((*profiles[*a].get(*i)?, *profiles[*b].get(*i)?)))
+                    .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+                    .unzip();
+                ReplicateCorrelation {
+                    scope,
+                    name: name.clone(),
+                    replicate_a: names[*a].clone(),
+                    replicate_b: names[*b].clone(),
+                    positions: x.len(),
+                    pearson: pearson(&x, &y),
+                    spearman: spearman(&x, &y),
+                }
+            })
+        })
+        .collect()
+}
+
+pub fn write_correlations<W: Write>(
+    writer: &mut W,
+    correlations: &[ReplicateCorrelation],
+) -> Result<()> {
+    writeln!(
+        writer,
+        "Scope\tName\tReplicate A\tReplicate B\tPositions\tPearson\tSpearman"
+    )?;
+    for c in correlations {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            c.scope,
+            c.name,
+            c.replicate_a,
+            c.replicate_b,
+            c.positions,
+            number(c.pearson),
+            number(c.spearman)
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,343 @@
+use std::{io::Write, str::FromStr};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    annotation::{complement, Annotations, Subtype, TranscriptPosition, TranscriptStructure},
+    stats::{mean, number, quantile, sd, sorted},
+    translator::{Strand, Translator},
+};
+
+/// Reactivity a position must exceed to be part of a site, given as a value,
+/// `zscore:K` (K standard deviations above the mean) or `percentile:P`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+    Value(f32),
+    ZScore(f32),
+    Percentile(f32),
+}
+
+impl FromStr for Threshold {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |x: &str| x.parse::<f32>().ok().filter(|x| x.is_finite());
+        let threshold = match s.split_once(':') {
+            Some(("zscore", k)) => number(k).map(Self::ZScore),
+            Some(("percentile", p)) => number(p)
+                .filter(|p| (0.0..=100.0).contains(p))
+                .map(Self::Percentile),
+            Some(_) => None,
+            None => number(s).map(Self::Value),
+        };
+        threshold.ok_or_else(|| format!("Invalid threshold: {}", s))
+    }
+}
+
+impl Threshold {
+    /// The reactivity cut-off over all values of `profile`, ignoring NaN.
+    pub fn resolve(&self, profile: &[f32]) -> f32 {
+        let values = sorted(profile);
+        if values.is_empty() {
+            return f32::NAN;
+        }
+        match self {
+            Self::Value(value) => *value,
+            // A single value has no spread, so it is its own threshold.
+            Self::ZScore(k) => mean(&values) + k * sd(&values).unwrap_or(0.0),
+            Self::Percentile(p) => quantile(&values, p / 100.0),
+        }
+    }
+}
+
+/// A codon of the CDS, numbered from the start codon.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Codon {
+    pub number: usize,
+    /// Position within the codon, 1 to 3.
+    pub position: usize,
+    /// Sense bases of the codon.
+    pub sequence: String,
+}
+
+/// A feature overlapping a site. The transcript position and codon are those
+/// of the site's peak, or of the first position of the site the feature
+/// covers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SiteFeature {
+    /// Model id, the systematic name qualified by the isoform if any.
+    pub id: String,
+    pub systematic_name: String,
+    pub standard_name: String,
+    pub strand: Strand,
+    pub subtypes: Vec<Subtype>,
+    /// 1-based position in the spliced transcript, `None` in introns.
+    pub transcript_position: Option<usize>,
+    pub codon: Option<Codon>,
+}
+
+/// A run of reactive positions. Ranges are input indices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Site {
+    pub start: usize,
+    pub end: usize,
+    /// Most reactive position.
+    pub peak: usize,
+    pub peak_value: f32,
+    /// Mean of the values of the site, ignoring NaN.
+    pub mean: f32,
+    /// Positions above the threshold.
+    pub reactive: usize,
+    pub features: Vec<SiteFeature>,
+}
+
+/// Finds the positions of `profile` above `threshold` within each chromosome
+/// and merges those at most `max_gap` positions apart into sites. Sites
+/// shorter than `min_length` are dropped.
+pub fn call_sites(
+    profile: &[f32],
+    threshold: f32,
+    max_gap: usize,
+    min_length: usize,
+    translator: &Translator,
+) -> Vec<Site> {
+    let mut sites: Vec<Site> = Vec::new();
+    for (_, offset, length) in translator.chromosomes() {
+        let mut current: Option<Site> = None;
+        for (i, value) in profile
+            .iter()
+            .copied()
+            .enumerate()
+            .take(offset + length)
+            .skip(offset)
+        {
+            if value.is_nan() || value <= threshold {
+                continue;
+            }
+            match current.as_mut() {
+                Some(site) if i - site.end - 1 <= max_gap => {
+                    site.end = i;
+                    site.reactive += 1;
+                    if value > site.peak_value {
+                        site.peak = i;
+                        site.peak_value = value;
+                    }
+                }
+                _ => {
+                    sites.extend(current.take());
+                    current = Some(Site {
+                        start: i,
+                        end: i,
+                        peak: i,
+                        peak_value: value,
+                        mean: f32::NAN,
+                        reactive: 1,
+                        features: Vec::new(),
+                    });
+                }
+            }
+        }
+        sites.extend(current);
+    }
+
+    sites.retain(|site| site.end - site.start + 1 >= min_length.max(1));
+    for site in sites.iter_mut() {
+        site.mean = mean(&sorted(&profile[site.start..=site.end]));
+    }
+    sites
+}
+
+/// The codon of a coding transcript at `position`, with bases read from the
+/// input `sequence`.
+fn codon(
+    structure: &TranscriptStructure,
+    strand: Strand,
+    position: usize,
+    sequence: &str,
+) -> Option<Codon> {
+    let (from_start, from_stop) = structure.codon_offsets(position)?;
+    if from_start < 1 || from_stop > -1 {
+        return None;
+    }
+    let index = match structure.locate(position)? {
+        TranscriptPosition::Exon { index, .. } => index,
+        TranscriptPosition::Intron { .. } => return None,
+    };
+    let within = (from_start as usize - 1) % 3;
+    let first = index - within;
+    let bases: Option<String> = (first..first + 3)
+        .map(|i| {
+            let index = structure.position(i)?;
+            let base = sequence.get(index..=index)?;
+            Some(match strand {
+                Strand::Plus => base,
+                Strand::Minus => complement(base),
+            })
+        })
+        .collect();
+    Some(Codon {
+        number: (from_start as usize - 1) / 3 + 1,
+        position: within + 1,
+        sequence: bases?,
+    })
+}
+
+/// Fills in the features overlapping each of `sites`, which must be sorted
+/// and must not overlap, with their subtypes, transcript positions and
+/// codons.
+pub fn annotate_sites(sites: &mut [Site], annotations: &Annotations, sequence: &str) {
+    let mut cursor = annotations.cursor();
+    for site in sites.iter_mut() {
+        let mut features: Vec<(SiteFeature, Option<&TranscriptStructure>, usize)> = Vec::new();
+        for position in site.start..=site.end {
+            for (label, structure, subtypes) in cursor.features(position) {
+                let id = label.id();
+                let index = match features.iter().position(|(f, _, _)| f.id == id) {
+                    Some(index) => index,
+                    None => {
+                        features.push((
+                            SiteFeature {
+                                id,
+                                systematic_name: label.systematic_name.clone(),
+                                standard_name: label.standard_name.clone(),
+                                strand: label.strand,
+                                subtypes: Vec::new(),
+                                transcript_position: None,
+                                codon: None,
+                            },
+                            structure,
+                            position,
+                        ));
+                        features.len() - 1
+                    }
+                };
+                let (feature, _, located) = &mut features[index];
+                if position == site.peak {
+                    *located = position;
+                }
+                for subtype in subtypes {
+                    if subtype != Subtype::Unknown && !feature.subtypes.contains(&subtype) {
+                        feature.subtypes.push(subtype);
+                    }
+                }
+            }
+        }
+
+        site.features = features
+            .into_iter()
+            .map(|(mut feature, structure, position)| {
+                if let Some(structure) = structure {
+                    if let Some(TranscriptPosition::Exon { index, .. }) = structure.locate(position)
+                    {
+                        feature.transcript_position = Some(index + 1);
+                    }
+                    feature.codon = codon(structure, feature.strand, position, sequence);
+                }
+                feature
+            })
+            .collect();
+    }
+}
+
+/// Genomic chromosome and 0-based, half-open range of `site`.
+fn genomic(site: &Site, translator: &Translator) -> Result<(String, usize, usize)> {
+    let (chromosome, start) = translator
+        .locate(site.start)
+        .with_context(|| format!("Position {} is not mapped", site.start))?;
+    Ok((
+        format!("chr{}", chromosome),
+        start - 1,
+        start + site.end - site.start,
+    ))
+}
+
+fn site_name(index: usize) -> String {
+    format!("site_{}", index + 1)
+}
+
+/// Writes `sites` as BED6. The score is the peak value scaled to 0–1000
+/// relative to the highest peak; the strand is that of the overlapping
+/// features when they agree.
+pub fn write_sites_bed<W: Write>(
+    writer: &mut W,
+    sites: &[Site],
+    translator: &Translator,
+) -> Result<()> {
+    let highest = sites.iter().map(|s| s.peak_value).fold(f32::NAN, f32::max);
+    for (i, site) in sites.iter().enumerate() {
+        let (chromosome, start, end) = genomic(site, translator)?;
+        let score = if highest > 0.0 {
+            (site.peak_value / highest * 1000.0)
+                .round()
+                .clamp(0.0, 1000.0) as usize
+        } else {
+            0
+        };
+        let strand = match site.features.first().map(|f| f.strand) {
+            Some(strand) if site.features.iter().all(|f| f.strand == strand) => match strand {
+                Strand::Plus => "+",
+                Strand::Minus => "-",
+            },
+            _ => ".",
+        };
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            chromosome,
+            start,
+            end,
+            site_name(i),
+            score,
+            strand
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes `sites` as a table with one value per overlapping feature,
+/// separated by `,`, in the feature columns.
+pub fn write_sites_table<W: Write>(
+    writer: &mut W,
+    sites: &[Site],
+    translator: &Translator,
+) -> Result<()> {
+    writeln!(
+        writer,
+        "Name\tChromosome\tStart\tEnd\tPeak\tPeak value\tMean\tReactive positions\tFeatures\tSubtypes\tTranscript position\tCodon number\tCodon position\tCodon"
+    )?;
+    let optional = |x: Option<usize>| x.map(|x| x.to_string()).unwrap_or_default();
+    for (i, site) in sites.iter().enumerate() {
+        let (chromosome, start, end) = genomic(site, translator)?;
+        let column = |f: &dyn Fn(&SiteFeature) -> String| {
+            site.features.iter().map(f).collect::<Vec<_>>().join(",")
+        };
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            site_name(i),
+            chromosome,
+            start + 1,
+            end,
+            start + 1 + site.peak - site.start,
+            number(site.peak_value),
+            number(site.mean),
+            site.reactive,
+            column(&|f| f.id.clone()),
+            column(&|f| f
+                .subtypes
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>()
+                .join(";")),
+            column(&|f| optional(f.transcript_position)),
+            column(&|f| optional(f.codon.as_ref().map(|c| c.number))),
+            column(&|f| optional(f.codon.as_ref().map(|c| c.position))),
+            column(&|f| f
+                .codon
+                .as_ref()
+                .map(|c| c.sequence.clone())
+                .unwrap_or_default()),
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,173 @@
+use std::{fmt::Display, str::FromStr};
+
+use rayon::prelude::*;
+
+use crate::{
+    model::FeatureModel,
+    stats::{mean, median, sorted},
+};
+
+/// Weighting of the values within a smoothing window.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kernel {
+    Mean,
+    Median,
+    /// Gaussian weights with a standard deviation of a quarter of the window.
+    Gaussian,
+}
+
+impl FromStr for Kernel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mean" => Ok(Self::Mean),
+            "median" => Ok(Self::Median),
+            "gaussian" => Ok(Self::Gaussian),
+            _ => Err(format!("Invalid smoothing kernel: {}", s)),
+        }
+    }
+}
+
+impl Display for Kernel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mean => write!(f, "mean"),
+            Self::Median => write!(f, "median"),
+            Self::Gaussian => write!(f, "Gaussian"),
+        }
+    }
+}
+
+/// A sliding window smoothing, given as `KERNEL[:WINDOW]`, e.g.
+/// `gaussian:9`. The window defaults to 5 nt and is centred on each
+/// position, so even windows are widened by one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Smoothing {
+    pub kernel: Kernel,
+    pub window: usize,
+}
+
+impl FromStr for Smoothing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kernel, window) = match s.split_once(':') {
+            Some((kernel, window)) => (
+                kernel.parse()?,
+                window
+                    .parse()
+                    .ok()
+                    .filter(|w| *w > 0)
+                    .ok_or_else(|| format!("Invalid smoothing window: {}", window))?,
+            ),
+            None => (s.parse()?, 5),
+        };
+        Ok(Self { kernel, window })
+    }
+}
+
+impl Smoothing {
+    pub fn column_name(&self) -> String {
+        format!("Smoothed {} ({} nt)", self.kernel, self.window)
+    }
+
+    /// Smooths one contiguous `segment`, ignoring NaN. Positions without a
+    /// value stay NaN; gaps are only filled by [`fill_gaps`].
+    pub fn smooth(&self, segment: &[f32]) -> Vec<f32> {
+        let half = self.window / 2;
+        let sigma = (self.window as f32 / 4.0).max(0.5);
+        (0..segment.len())
+            .map(|i| {
+                if segment[i].is_nan() {
+                    return f32::NAN;
+                }
+                let start = i.saturating_sub(half);
+                let end = (i + half).min(segment.len() - 1);
+                let neighbours = (start..=end).filter(|j| !segment[*j].is_nan());
+                match self.kernel {
+                    Kernel::Mean => mean(&neighbours.map(|j| segment[j]).collect::<Vec<_>>()),
+                    Kernel::Median => median(&sorted(&segment[start..=end])),
+                    Kernel::Gaussian => {
+                        let (mut sum, mut weights) = (0.0, 0.0);
+                        for j in neighbours {
+                            let d = j as f32 - i as f32;
+                            let weight = (-d * d / (2.0 * sigma * sigma)).exp();
+                            sum += weight * segment[j];
+                            weights += weight;
+                        }
+                        sum / weights
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Fills runs of at most `max_gap` NaN values by linear interpolation between
+/// the values on either side. Longer runs and runs at either end of `segment`
+/// are left as NaN.
+pub fn fill_gaps(segment: &[f32], max_gap: usize) -> Vec<f32> {
+    let mut filled = segment.to_vec();
+    let mut last = None;
+    for (i, value) in segment.iter().enumerate() {
+        if value.is_nan() {
+            continue;
+        }
+        if let Some(previous) = last {
+            let gap = i - previous - 1;
+            if gap > 0 && gap <= max_gap {
+                let (a, b) = (segment[previous], *value);
+                for (j, slot) in filled.iter_mut().enumerate().take(i).skip(previous + 1) {
+                    let t = (j - previous) as f32 / (i - previous) as f32;
+                    *slot = a + (b - a) * t;
+                }
+            }
+        }
+        last = Some(i);
+    }
+    filled
+}
+
+/// Ranges of the profile processed on their own: each exonic block and each
+/// intron of every model, so that values never spread across a splice
+/// junction, into a neighbouring feature or between overlapping features on
+/// opposite strands.
+fn segments(models: &[FeatureModel], length: usize) -> Vec<(usize, usize)> {
+    models
+        .iter()
+        .flat_map(|model| {
+            let mut ranges = model.blocks();
+            ranges.extend(model.introns.iter().copied());
+            ranges
+        })
+        .filter(|(start, _)| *start < length)
+        .map(|(start, end)| (start, end.min(length - 1)))
+        .collect()
+}
+
+/// Applies `transform` to every segment of `profile` within `models`.
+/// Positions outside of features are left empty; positions shared by several
+/// models take the value of the first one.
+pub fn by_segment<F>(profile: &[f32], models: &[FeatureModel], transform: F) -> Vec<f32>
+where
+    F: Fn(&[f32]) -> Vec<f32> + Sync,
+{
+    let segments = segments(models, profile.len());
+    let transformed: Vec<Vec<f32>> = segments
+        .par_iter()
+        .map(|(start, end)| transform(&profile[*start..=*end]))
+        .collect();
+
+    let mut result = vec![f32::NAN; profile.len()];
+    let mut assigned = vec![false; profile.len()];
+    for ((start, _), values) in segments.iter().zip(transformed) {
+        for (i, value) in values.into_iter().enumerate() {
+            if !assigned[start + i] {
+                result[start + i] = value;
+                assigned[start + i] = true;
+            }
+        }
+    }
+    result
+}
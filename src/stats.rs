@@ -0,0 +1,52 @@
+//! Summary statistics and number formatting shared by the reports.
+
+/// The values of `values` that are not NaN, in ascending order.
+pub fn sorted(values: &[f32]) -> Vec<f32> {
+    let mut sorted: Vec<f32> = values.iter().copied().filter(|x| !x.is_nan()).collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    sorted
+}
+
+/// Mean of `values`, NaN when empty.
+pub fn mean(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        return f32::NAN;
+    }
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+/// Sample standard deviation of `values`, `None` with fewer than two values.
+pub fn sd(values: &[f32]) -> Option<f32> {
+    if values.len() < 2 {
+        return None;
+    }
+    let mean = mean(values);
+    let variance =
+        values.iter().map(|x| (x - mean).powi(2)).sum::<f32>() / (values.len() - 1) as f32;
+    Some(variance.sqrt())
+}
+
+/// Linearly interpolated `p`-th quantile of ascending `sorted` values, NaN
+/// when empty.
+pub fn quantile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return f32::NAN;
+    }
+    let rank = p * (sorted.len() - 1) as f32;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f32)
+}
+
+/// Median of ascending `sorted` values, NaN when empty.
+pub fn median(sorted: &[f32]) -> f32 {
+    quantile(sorted, 0.5)
+}
+
+/// `x` with four decimals for tables, empty when NaN.
+pub fn number(x: f32) -> String {
+    if x.is_nan() {
+        String::new()
+    } else {
+        format!("{:.4}", x)
+    }
+}
@@ -0,0 +1,219 @@
+use std::{collections::BTreeMap, fmt::Display, io::Write, str::FromStr};
+
+use anyhow::Result;
+
+use crate::{
+    stats::{mean, number},
+    summary::gini,
+    transcript::TranscriptProfile,
+    translator::{Translator, YeastChromosome},
+};
+
+/// A per-window summary of reactivities.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Metric {
+    /// Gini index: close to 1 when a few bases carry all reactivity, as in
+    /// structured regions, close to 0 when it is evenly spread.
+    Gini,
+    /// Shannon entropy, in bits, of the reactivities taken as a distribution.
+    Entropy,
+    Mean,
+    /// Fraction of values above the high reactivity threshold.
+    HighFraction,
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gini" => Ok(Self::Gini),
+            "entropy" => Ok(Self::Entropy),
+            "mean" => Ok(Self::Mean),
+            "high" => Ok(Self::HighFraction),
+            _ => Err(format!("Invalid structure metric: {}", s)),
+        }
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Gini => write!(f, "Gini index"),
+            Self::Entropy => write!(f, "Shannon entropy"),
+            Self::Mean => write!(f, "Mean reactivity"),
+            Self::HighFraction => write!(f, "High reactivity fraction"),
+        }
+    }
+}
+
+/// Shannon entropy, in bits, of `values` scaled to sum to one; negative values
+/// count as zero.
+pub fn entropy(values: &[f32]) -> f32 {
+    let total: f32 = values.iter().map(|x| x.max(0.0)).sum();
+    if total <= 0.0 {
+        return f32::NAN;
+    }
+    -values
+        .iter()
+        .map(|x| x.max(0.0) / total)
+        .filter(|p| *p > 0.0)
+        .map(|p| p * p.log2())
+        .sum::<f32>()
+}
+
+/// Metrics of a window of a transcript. Positions are 1-based and in
+/// transcript coordinates, 5' to 3'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowMetrics {
+    pub start: usize,
+    pub end: usize,
+    /// Positions with a value.
+    pub values: usize,
+    pub gini: f32,
+    pub entropy: f32,
+    pub mean: f32,
+    pub high_fraction: f32,
+}
+
+impl WindowMetrics {
+    pub fn get(&self, metric: Metric) -> f32 {
+        match metric {
+            Metric::Gini => self.gini,
+            Metric::Entropy => self.entropy,
+            Metric::Mean => self.mean,
+            Metric::HighFraction => self.high_fraction,
+        }
+    }
+}
+
+/// Options of the sliding windows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WindowOptions {
+    pub size: usize,
+    pub step: usize,
+    /// Windows with fewer values have NaN metrics.
+    pub min_values: usize,
+    /// Values above this are highly reactive.
+    pub high_reactivity: f32,
+}
+
+/// Metrics of windows of `options.size` positions every `options.step`
+/// positions along `values`, a transcript profile. Transcripts shorter than a
+/// window have none.
+pub fn window_metrics(values: &[f32], options: &WindowOptions) -> Vec<WindowMetrics> {
+    let mut windows = Vec::new();
+    let mut start = 0;
+    while options.size > 0 && start + options.size <= values.len() {
+        let end = start + options.size - 1;
+        let present: Vec<f32> = values[start..=end]
+            .iter()
+            .copied()
+            .filter(|x| !x.is_nan())
+            .collect();
+        let n = present.len();
+        let tested = n >= options.min_values.max(1);
+        let metric = |f: &dyn Fn(&[f32]) -> f32| if tested { f(&present) } else { f32::NAN };
+        windows.push(WindowMetrics {
+            start: start + 1,
+            end: end + 1,
+            values: n,
+            gini: metric(&gini),
+            entropy: metric(&entropy),
+            mean: metric(&mean),
+            high_fraction: metric(&|v| {
+                v.iter().filter(|x| **x > options.high_reactivity).count() as f32 / v.len() as f32
+            }),
+        });
+        start += options.step.max(1);
+    }
+    windows
+}
+
+pub fn write_window_table<W: Write>(
+    writer: &mut W,
+    transcripts: &[(TranscriptProfile, Vec<WindowMetrics>)],
+) -> Result<()> {
+    writeln!(
+        writer,
+        "Transcript\tSystematic name\tStandard name\tStart\tEnd\tValues\t{}\t{}\t{}\t{}",
+        Metric::Mean,
+        Metric::Gini,
+        Metric::Entropy,
+        Metric::HighFraction
+    )?;
+    for (transcript, windows) in transcripts {
+        for window in windows {
+            writeln!(
+                writer,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                transcript.id,
+                transcript.systematic_name,
+                transcript.standard_name,
+                window.start,
+                window.end,
+                window.values,
+                number(window.mean),
+                number(window.gini),
+                number(window.entropy),
+                number(window.high_fraction)
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `metric` as a bedGraph track in genomic coordinates. Every position
+/// gets the mean of all windows covering it, over all transcripts, so that
+/// overlapping windows, isoforms and genes give disjoint intervals. Windows
+/// without a value and positions that are not mapped are left out.
+pub fn write_bedgraph<W: Write>(
+    writer: &mut W,
+    transcripts: &[(TranscriptProfile, Vec<WindowMetrics>)],
+    metric: Metric,
+    options: &WindowOptions,
+    translator: &Translator,
+) -> Result<()> {
+    let mut totals: BTreeMap<usize, (f64, usize)> = BTreeMap::new();
+    for (transcript, windows) in transcripts {
+        for window in windows {
+            let value = window.get(metric);
+            if value.is_nan() {
+                continue;
+            }
+            for i in &transcript.indices[window.start - 1..window.end] {
+                let total = totals.entry(*i).or_default();
+                total.0 += value as f64;
+                total.1 += 1;
+            }
+        }
+    }
+
+    let mut intervals: Vec<(YeastChromosome, usize, usize, f32)> = Vec::new();
+    for (i, (sum, count)) in totals {
+        let Some((chromosome, position)) = translator.locate(i) else {
+            continue;
+        };
+        let value = (sum / count as f64) as f32;
+        match intervals.last_mut() {
+            Some((c, _, end, v)) if *c == chromosome && *end + 1 == position && *v == value => {
+                *end = position;
+            }
+            _ => intervals.push((chromosome, position - 1, position, value)),
+        }
+    }
+
+    writeln!(
+        writer,
+        "track type=bedGraph name=\"{}\" description=\"{} in {} nt windows\"",
+        metric, metric, options.size
+    )?;
+    for (chromosome, start, end, value) in intervals {
+        writeln!(
+            writer,
+            "chr{}\t{}\t{}\t{:.4}",
+            chromosome, start, end, value
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,183 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+
+use crate::{
+    model::FeatureModel,
+    stats::{mean, median, number, sorted},
+    translator::{Strand, Translator},
+};
+
+/// Reactivity statistics of one region of a feature.
+#[derive(Debug, Clone)]
+pub struct RegionSummary {
+    pub systematic_name: String,
+    pub standard_name: String,
+    pub isoform: String,
+    pub chromosome: String,
+    pub strand: Strand,
+    /// `UTR 5'`, `CDS`, `Exon 1`, `Intron 1`, `UTR 3'` or `Feature`.
+    pub region: String,
+    /// 1-based chromosome positions of the outermost nucleotides.
+    pub start: usize,
+    pub end: usize,
+    pub length: usize,
+    pub gc_content: f32,
+    /// Number of non-NaN profile values.
+    pub values: usize,
+    pub coverage: f32,
+    pub mean: f32,
+    pub median: f32,
+    pub max: f32,
+    pub gini: f32,
+}
+
+/// Gini index of non-negative values; negative reactivities count as 0.
+pub fn gini(values: &[f32]) -> f32 {
+    let mut sorted: Vec<f32> = values.iter().map(|x| x.max(0.0)).collect();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let n = sorted.len() as f32;
+    let sum: f32 = sorted.iter().sum();
+    if sorted.is_empty() || sum <= 0.0 {
+        return f32::NAN;
+    }
+    let weighted: f32 = sorted
+        .iter()
+        .enumerate()
+        .map(|(i, x)| (i + 1) as f32 * x)
+        .sum();
+    2.0 * weighted / (n * sum) - (n + 1.0) / n
+}
+
+/// The regions of `model` in transcript order, each as the input index
+/// intervals it consists of. Exons include the UTRs, the CDS excludes introns.
+fn regions(model: &FeatureModel) -> Vec<(String, Vec<(usize, usize)>)> {
+    let mut exons = if model.exons.is_empty() {
+        Vec::new()
+    } else {
+        model.blocks()
+    };
+    let mut introns = model.introns.clone();
+    if model.strand == Strand::Minus {
+        exons.reverse();
+        introns.reverse();
+    }
+
+    let mut regions = Vec::new();
+    if let Some(utr) = model.utr5 {
+        regions.push(("UTR 5'".to_string(), vec![utr]));
+    }
+    if model.is_coding() {
+        regions.push(("CDS".to_string(), model.exons.clone()));
+    }
+    for (i, exon) in exons.iter().enumerate() {
+        regions.push((format!("Exon {}", i + 1), vec![*exon]));
+        if let Some(intron) = introns.get(i) {
+            regions.push((format!("Intron {}", i + 1), vec![*intron]));
+        }
+    }
+    if let Some(utr) = model.utr3 {
+        regions.push(("UTR 3'".to_string(), vec![utr]));
+    }
+    if exons.is_empty() {
+        regions.push(("Feature".to_string(), vec![model.range]));
+    }
+    regions
+}
+
+/// Summarises every region of `model`, i.e. the ranges the annotator paints
+/// as subtypes. `sequence` and `profile` are indexed like the input.
+pub fn summarize_model(
+    model: &FeatureModel,
+    translator: &Translator,
+    sequence: &str,
+    profile: &[f32],
+) -> Result<Vec<RegionSummary>> {
+    let offset = translator
+        .chromosome_offset(&model.chromosome)
+        .with_context(|| format!("Chromosome {} is not mapped", model.chromosome))?;
+
+    let summaries = regions(model)
+        .into_iter()
+        .map(|(region, intervals)| {
+            let positions = || intervals.iter().flat_map(|(start, end)| *start..=*end);
+            let length = positions().count();
+            let gc = positions()
+                .filter(|i| matches!(sequence.as_bytes().get(*i), Some(b'G' | b'C')))
+                .count();
+            let values = sorted(
+                &positions()
+                    .filter_map(|i| profile.get(i).copied())
+                    .collect::<Vec<_>>(),
+            );
+            let mean = mean(&values);
+
+            let start = intervals.iter().map(|(s, _)| *s).min().unwrap_or(0);
+            let end = intervals.iter().map(|(_, e)| *e).max().unwrap_or(0);
+            RegionSummary {
+                systematic_name: model.systematic_name.clone(),
+                standard_name: model.standard_name.clone(),
+                isoform: model.isoform.clone().unwrap_or_default(),
+                chromosome: format!("chr{}", model.chromosome),
+                strand: model.strand,
+                region,
+                start: start - offset + 1,
+                end: end - offset + 1,
+                length,
+                gc_content: gc as f32 / length as f32,
+                values: values.len(),
+                coverage: values.len() as f32 / length as f32,
+                mean,
+                median: median(&values),
+                max: values.last().copied().unwrap_or(f32::NAN),
+                gini: gini(&values),
+            }
+        })
+        .collect();
+    Ok(summaries)
+}
+
+/// Summarises all regions of all `models`, keeping their order.
+pub fn summarize(
+    models: &[FeatureModel],
+    translator: &Translator,
+    sequence: &str,
+    profile: &[f32],
+) -> Result<Vec<RegionSummary>> {
+    let summaries = models
+        .par_iter()
+        .map(|model| summarize_model(model, translator, sequence, profile))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(summaries.into_iter().flatten().collect())
+}
+
+pub fn write_summary<W: Write>(writer: &mut W, summaries: &[RegionSummary]) -> Result<()> {
+    writeln!(
+        writer,
+        "Systematic name\tStandard name\tIsoform\tChromosome\tStrand\tRegion\tStart\tEnd\tLength\tGC content\tValues\tCoverage\tMean\tMedian\tMax\tGini"
+    )?;
+    for s in summaries {
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            s.systematic_name,
+            s.standard_name,
+            s.isoform,
+            s.chromosome,
+            s.strand,
+            s.region,
+            s.start,
+            s.end,
+            s.length,
+            number(s.gc_content),
+            s.values,
+            number(s.coverage),
+            number(s.mean),
+            number(s.median),
+            number(s.max),
+            number(s.gini)
+        )?;
+    }
+    Ok(())
+}
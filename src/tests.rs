@@ -1,17 +1,91 @@
 #[cfg(test)]
 mod tests {
     use crate::{
+        annotation::{
+            Annotations, GenomeCoordinates, Label, Subtype, SubtypeRules, TranscriptPosition,
+            TranscriptStructure,
+        },
+        chemistry::{Chemistry, ProbeArgs},
+        compare::{
+            align, annotate_hits, find_hits, test_windows, wilcoxon_signed_rank, Correction,
+        },
+        diagnostics::Reason,
+        export::{write_bed12, write_gff3, write_gtf, CoordinateSpace},
         fasta::load_fasta_gz,
         fasta::{load_utr_fasta_gz, FastaType},
+        gene::{
+            genes_from_gff, infer_missing_utrs, Exon, Gene, Intron, Transcript, Utr, UtrFallback,
+        },
+        gff::{load_gff, Gff, GffRecord},
+        isoform::{select_isoforms, IsoformSelection, UtrCatalog},
         load_genome_gz,
-        translator::{GenomicRange, Translator, YeastChromosome},
+        metagene::{landmark_curve, scaled_curves, GeneNormalisation, Landmark},
+        model::{feature_category, find_regions, FeatureModel},
+        normalisation::{Method, Normalisation, Scope},
+        pipeline::{annotate, ModelArgs},
+        qc::{qc_report, write_qc_json, write_qc_summary},
+        replicate::{check_sequence, correlations, merge, pearson, spearman},
+        sites::{annotate_sites, call_sites, write_sites_bed, write_sites_table, Threshold},
+        smoothing::{by_segment, fill_gaps, Kernel, Smoothing},
+        stats::{mean, median, number, quantile, sd, sorted},
+        structure::{
+            entropy, window_metrics, write_bedgraph, Metric, WindowMetrics, WindowOptions,
+        },
+        summary::{gini, summarize_model},
+        transcript::{ProfileFormat, TranscriptProfile},
+        translator::{GenomicRange, Strand, Translator, YeastChromosome},
+        utr::{CoverageThreshold, NanRun, NoTrimming, UtrBoundaryStrategy, UtrRegion},
     };
-    use std::path::Path;
+    use clap::Parser;
+    use std::{collections::HashMap, path::Path};
 
     fn init() {
         let _ = env_logger::builder().is_test(true).try_init();
     }
 
+    /// An ORF named after EFB1 on chromosome I, with introns between the
+    /// `exons`. Tests change the other fields with struct update syntax.
+    fn model(
+        strand: Strand,
+        range: (usize, usize),
+        exons: Vec<(usize, usize)>,
+        utr5: Option<(usize, usize)>,
+        utr3: Option<(usize, usize)>,
+    ) -> FeatureModel {
+        let introns = exons
+            .windows(2)
+            .map(|pair| (pair[0].1 + 1, pair[1].0 - 1))
+            .collect();
+        FeatureModel {
+            systematic_name: "YAL003W".to_string(),
+            standard_name: "EFB1".to_string(),
+            category: "ORF".to_string(),
+            qualifier: None,
+            coding: true,
+            chromosome: YeastChromosome::I,
+            strand,
+            range,
+            exons,
+            introns,
+            utr5,
+            utr3,
+            utr5_inferred: false,
+            utr3_inferred: false,
+            isoform: None,
+        }
+    }
+
+    fn label(systematic_name: &str, standard_name: &str, strand: Strand) -> Label {
+        Label {
+            category: "ORF".to_string(),
+            systematic_name: systematic_name.to_string(),
+            standard_name: standard_name.to_string(),
+            qualifier: String::new(),
+            isoform: String::new(),
+            strand,
+        }
+    }
+
     #[test]
     fn fasta_genomic() {
         let fasta = load_fasta_gz(Path::new("tests/genomic.fasta.gz"));
@@ -35,6 +109,7 @@ mod tests {
         assert_eq!(gene.fasta_type(), FastaType::Gene);
         assert_eq!(gene.systematic_name(), "YAL067W-A");
         assert_eq!(gene.standard_name(), "YAL067W-A");
+        assert_eq!(gene.feature_type().unwrap(), "Uncharacterized ORF");
         assert_eq!(
             gene.genomic_range(),
             GenomicRange {
@@ -211,4 +286,1300 @@ mod tests {
             assert_eq!(&genome[start..=end], fasta.sequence());
         }
     }
+
+    #[test]
+    fn annotation_intervals() {
+        let mut annotations = Annotations::new(1);
+        let track = annotations.track_mut(0);
+        track.add_feature(
+            2,
+            8,
+            Label {
+                qualifier: "Verified".to_string(),
+                ..label("YAL003W", "EFB1", Strand::Plus)
+            },
+        );
+        track.set_subtype(2, 8, Subtype::Unknown);
+        track.set_subtype(2, 3, Subtype::Utr5);
+        track.set_subtype(3, 7, Subtype::Exon);
+        track.set_subtype(5, 5, Subtype::Intron);
+
+        let mut cursor = annotations.cursor();
+        let subtypes: Vec<String> = (0..10).map(|i| cursor.row(i)[1].to_string()).collect();
+        assert_eq!(
+            subtypes,
+            vec!["", "", "UTR 5'", "Exon", "Exon", "Intron", "Exon", "Exon", "?", ""]
+        );
+
+        let mut cursor = annotations.cursor();
+        assert_eq!(cursor.row(1), vec!["", "", "", "", "", ""]);
+        assert_eq!(
+            cursor.row(4),
+            vec!["ORF", "Exon", "YAL003W", "EFB1", "Verified", ""]
+        );
+
+        annotations.set_subtype_rules(SubtypeRules {
+            precedence: "utr5,exon".parse().unwrap(),
+            all_subtypes: false,
+            flag_conflicts: true,
+        });
+        assert_eq!(annotations.column_names().len(), 7);
+        let mut cursor = annotations.cursor();
+        assert_eq!(cursor.row(2)[1], "UTR 5'");
+        assert_eq!(cursor.row(3)[1], "UTR 5'");
+        assert_eq!(cursor.row(3)[6], "yes");
+        assert_eq!(cursor.row(4)[6], "");
+        assert_eq!(cursor.row(5)[1], "Exon");
+
+        annotations.set_subtype_rules(SubtypeRules {
+            all_subtypes: true,
+            ..Default::default()
+        });
+        let mut cursor = annotations.cursor();
+        assert_eq!(cursor.row(3)[1], "Exon;UTR 5'");
+        assert_eq!(cursor.row(5)[1], "Intron;Exon");
+        assert_eq!(cursor.row(8)[1], "?");
+    }
+
+    #[test]
+    fn annotation_merge() {
+        let mut first = Annotations::new(1);
+        first
+            .track_mut(0)
+            .add_feature(0, 2, label("A", "A", Strand::Plus));
+        let mut second = Annotations::new(2);
+        second
+            .track_mut(0)
+            .add_feature(5, 6, label("B", "B", Strand::Plus));
+        second
+            .track_mut(1)
+            .add_feature(5, 5, label("C", "C", Strand::Plus));
+
+        let merged = Annotations::merge(vec![first, second]);
+        assert_eq!(merged.track_count(), 2);
+
+        let mut cursor = merged.cursor();
+        assert_eq!(
+            cursor.row(1),
+            vec!["ORF", "", "A", "A", "", "", "", "", "", "", "", ""]
+        );
+        assert_eq!(
+            cursor.row(5),
+            vec!["ORF", "", "B", "B", "", "", "ORF", "", "C", "C", "", ""]
+        );
+    }
+
+    #[test]
+    fn export_bed12() {
+        let model = FeatureModel {
+            qualifier: Some("Verified".to_string()),
+            ..model(
+                Strand::Plus,
+                (90, 200),
+                vec![(100, 119), (150, 180)],
+                Some((90, 99)),
+                Some((181, 200)),
+            )
+        };
+        let translator = Translator::new("");
+
+        let mut buffer = Vec::new();
+        write_bed12(
+            &mut buffer,
+            std::slice::from_ref(&model),
+            &translator,
+            CoordinateSpace::Input,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "input\t90\t201\tYAL003W\t0\t+\t100\t181\t0\t2\t30,51,\t0,60,\n"
+        );
+
+        // Overlapping CDS pieces of a frameshift give a single block.
+        let frameshift = FeatureModel {
+            exons: vec![(100, 130), (125, 180)],
+            introns: Vec::new(),
+            ..model.clone()
+        };
+        let mut buffer = Vec::new();
+        write_bed12(
+            &mut buffer,
+            std::slice::from_ref(&frameshift),
+            &translator,
+            CoordinateSpace::Input,
+        )
+        .unwrap();
+        assert!(String::from_utf8(buffer)
+            .unwrap()
+            .ends_with("\t1\t111,\t0,\n"));
+
+        // Attribute values are percent-encoded in GFF3.
+        let escaped = FeatureModel {
+            standard_name: "A;B=C,D&E".to_string(),
+            category: "ORF%".to_string(),
+            ..model
+        };
+        let mut buffer = Vec::new();
+        write_gff3(&mut buffer, &[escaped], &translator, CoordinateSpace::Input).unwrap();
+        let gff3 = String::from_utf8(buffer).unwrap();
+        assert!(gff3.contains(";gene=A%3BB%3DC%2CD%26E;Note=ORF%25;"));
+
+        // Overlapping CDS pieces give one exon record, quotes are escaped in
+        // GTF and UTRs use the standard feature types.
+        let quoted = FeatureModel {
+            standard_name: "A\"B".to_string(),
+            ..frameshift
+        };
+        let mut buffer = Vec::new();
+        write_gff3(
+            &mut buffer,
+            std::slice::from_ref(&quoted),
+            &translator,
+            CoordinateSpace::Input,
+        )
+        .unwrap();
+        let gff3 = String::from_utf8(buffer).unwrap();
+        assert_eq!(gff3.matches("\texon\t").count(), 1);
+        let mut buffer = Vec::new();
+        write_gtf(&mut buffer, &[quoted], &translator, CoordinateSpace::Input).unwrap();
+        let gtf = String::from_utf8(buffer).unwrap();
+        assert_eq!(gtf.matches("\texon\t").count(), 1);
+        assert!(gtf.contains("gene_name \"A\\\"B\";"));
+        assert!(gtf.contains("\tfive_prime_utr\t") && gtf.contains("\tthree_prime_utr\t"));
+    }
+
+    #[test]
+    fn gff_hierarchy() {
+        let gff = load_gff(Path::new("tests/sample.gff")).unwrap();
+        assert_eq!(gff.records.len(), 11);
+
+        let roots: Vec<&str> = gff.roots().filter_map(|r| r.id()).collect();
+        assert_eq!(roots, vec!["chrI", "YAL003W", "tP(UGG)A", "ARS_mt"]);
+
+        let gene = gff.roots().find(|r| r.id() == Some("YAL003W")).unwrap();
+        assert_eq!(gene.strand, Some(Strand::Plus));
+        assert_eq!(gene.attribute("gene"), Some("EFB1"));
+        assert_eq!(gene.attribute("Alias"), Some("EFB1,TEF5"));
+        assert_eq!(
+            gene.genomic_range(),
+            Some(GenomicRange {
+                chromosome: YeastChromosome::I,
+                start: 142174,
+                end: 143160,
+            })
+        );
+
+        let kinds: Vec<&str> = gff
+            .descendants(gene)
+            .iter()
+            .map(|r| r.kind.as_str())
+            .collect();
+        assert_eq!(kinds, vec!["mRNA", "CDS", "intron", "CDS"]);
+
+        let ars = gff.roots().find(|r| r.id() == Some("ARS_mt")).unwrap();
+        assert_eq!(ars.strand, None);
+        assert_eq!(ars.chromosome(), Some(YeastChromosome::Mito));
+    }
+
+    #[test]
+    fn feature_categories() {
+        let category = |kind: &str| feature_category(kind).0;
+        assert_eq!(
+            feature_category("Dubious ORF"),
+            ("ORF".to_string(), Some("Dubious".to_string()))
+        );
+        assert_eq!(category("tRNA_gene"), "tRNA");
+        assert_eq!(category("ncRNA_gene"), "ncRNA");
+        assert_eq!(category("telomerase_RNA_gene"), "telomerase RNA");
+        assert_eq!(category("long_terminal_repeat"), "LTR");
+        assert_eq!(category("LTR_retrotransposon"), "Ty element");
+        assert_eq!(category("X_element"), "X element");
+        assert_eq!(category("ARS"), "ARS");
+    }
+
+    #[test]
+    fn utr_strategies() {
+        let nan = f32::NAN;
+        let profile = vec![
+            nan, nan, 0.1, nan, nan, nan, nan, nan, nan, 0.4, 0.3, 0.2, 0.5, 0.1, 0.2,
+        ];
+        // 5' UTR on the plus strand ends at the CDS on its right side.
+        let utr = UtrRegion {
+            start: 1,
+            end: 14,
+            is_5p: true,
+            strand: Strand::Plus,
+        };
+        let refined = NanRun::default().refine(&utr, &profile);
+        assert_eq!(refined.range, Some((9, 14)));
+
+        // Windows reaching past the beginning of the profile count as NaN.
+        let covered = UtrRegion {
+            start: 0,
+            end: 3,
+            is_5p: true,
+            strand: Strand::Plus,
+        };
+        let refined = NanRun::default().refine(&covered, &profile[9..13]);
+        assert_eq!(refined.range, Some((0, 3)));
+
+        // 5' UTR on the minus strand is cut on its right side.
+        let utr = UtrRegion {
+            start: 9,
+            end: 14,
+            is_5p: true,
+            strand: Strand::Minus,
+        };
+        let refined = NanRun::default().refine(&utr, &profile);
+        assert_eq!(refined.range, Some((9, 14)));
+        let refined = CoverageThreshold {
+            window: 4,
+            min_fraction: 1.0,
+        }
+        .refine(&utr, &profile);
+        assert_eq!(refined.range, Some((9, 11)));
+
+        #[derive(clap::Parser)]
+        struct Cli {
+            #[command(flatten)]
+            models: ModelArgs,
+        }
+        let parse = |window: &str| Cli::try_parse_from(["test", "--utr-window", window]);
+        assert_eq!(parse("4").unwrap().models.utr_window, 4);
+        assert!(parse("0").is_err());
+    }
+
+    #[test]
+    fn isoform_selection() {
+        let mut catalog = UtrCatalog::new();
+        catalog.add_utr_fasta(Path::new("tests/utr.fasta.gz"), "SGD");
+        catalog
+            .add_isoform_table(Path::new("tests/isoforms.tsv"), "TIF")
+            .unwrap();
+
+        let cds = GenomicRange {
+            chromosome: YeastChromosome::I,
+            start: 7235,
+            end: 9015,
+        };
+        let isoforms = catalog.isoforms("YAL067C", &cds);
+        let labels: Vec<String> = isoforms.iter().map(|i| i.label()).collect();
+        assert_eq!(labels, vec!["SGD:id001", "TIF:2", "TIF:3"]);
+        let tif = &isoforms[1];
+        assert_eq!(tif.utr5.as_ref().unwrap().range.start, 9016);
+        assert_eq!(tif.utr5.as_ref().unwrap().range.end, 9100);
+        assert_eq!(tif.utr3.as_ref().unwrap().range.start, 7000);
+        assert_eq!(tif.utr3.as_ref().unwrap().range.end, 7234);
+
+        let all = select_isoforms(isoforms.clone(), IsoformSelection::All);
+        assert_eq!(all.len(), 3);
+
+        let major = select_isoforms(isoforms.clone(), IsoformSelection::Major);
+        assert_eq!(major.len(), 1);
+        assert_eq!(major[0].label(), "TIF:3");
+
+        let longest = select_isoforms(isoforms, IsoformSelection::Longest);
+        assert_eq!(longest.len(), 1);
+        assert_eq!(longest[0].label(), "TIF:2");
+        assert_eq!(longest[0].utr5.as_ref().unwrap().len(), 85);
+    }
+
+    #[test]
+    fn gene_structure() {
+        let gff = load_gff(Path::new("tests/sample.gff")).unwrap();
+        let (genes, _) = genes_from_gff(&gff);
+        let names: Vec<&str> = genes.iter().map(|g| g.systematic_name.as_str()).collect();
+        assert_eq!(names, vec!["YAL003W", "tP(UGG)A", "ARS_mt"]);
+
+        let efb1 = &genes[0];
+        assert!(efb1.coding);
+        assert_eq!(efb1.standard_name, "EFB1");
+        assert_eq!(efb1.cds_range(), Some((142174, 143160)));
+        let transcript = &efb1.transcripts[0];
+        assert_eq!(
+            transcript.exons,
+            vec![
+                Exon {
+                    start: 142174,
+                    end: 142253
+                },
+                Exon {
+                    start: 142620,
+                    end: 143160
+                }
+            ]
+        );
+        assert_eq!(
+            transcript.introns,
+            vec![Intron {
+                start: 142254,
+                end: 142619
+            }]
+        );
+
+        let trna = &genes[1];
+        assert!(!trna.coding);
+        assert_eq!(trna.category, "tRNA");
+        assert_eq!(trna.transcripts[0].exons.len(), 2);
+
+        // A 5' UTR downstream of the CDS is inconsistent.
+        let mut broken = efb1.clone();
+        broken.transcripts[0].utr5 = Some(Utr {
+            start: 143161,
+            end: 143200,
+            source: "test:1".to_string(),
+            inferred: false,
+        });
+        assert!(broken.validate().is_err());
+        broken.transcripts[0].utr5 = Some(Utr {
+            start: 142100,
+            end: 142173,
+            source: "test:1".to_string(),
+            inferred: false,
+        });
+        assert!(broken.validate().is_ok());
+    }
+
+    #[test]
+    fn utr_fallback() {
+        let gff = load_gff(Path::new("tests/sample.gff")).unwrap();
+        let range = |utr: &Option<Utr>| utr.as_ref().map(|u| (u.start, u.end));
+
+        let lengths = HashMap::from([(YeastChromosome::I, 230218)]);
+        let (mut genes, _) = genes_from_gff(&gff);
+        infer_missing_utrs(&mut genes, UtrFallback::Fixed, 100, &lengths);
+        let transcript = &genes[0].transcripts[0];
+        assert_eq!(range(&transcript.utr5), Some((142074, 142173)));
+        assert_eq!(range(&transcript.utr3), Some((143161, 143260)));
+        assert!(transcript.utr5.as_ref().unwrap().inferred);
+        // Only ORFs get UTRs.
+        assert_eq!(genes[1].transcripts[0].utr5, None);
+
+        let (mut genes, _) = genes_from_gff(&gff);
+        infer_missing_utrs(&mut genes, UtrFallback::Neighbour, 50000, &lengths);
+        let transcript = &genes[0].transcripts[0];
+        assert_eq!(range(&transcript.utr5), Some((92174, 142173)));
+        assert_eq!(range(&transcript.utr3), Some((143161, 181140)));
+
+        let (mut genes, _) = genes_from_gff(&gff);
+        infer_missing_utrs(&mut genes, UtrFallback::None, 100, &lengths);
+        assert_eq!(genes[0].transcripts[0].utr5, None);
+
+        // Genes near the end of the chromosome get shorter 3' UTRs, or none.
+        let (mut genes, _) = genes_from_gff(&gff);
+        let near_end = HashMap::from([(YeastChromosome::I, 143200)]);
+        infer_missing_utrs(&mut genes, UtrFallback::Fixed, 100, &near_end);
+        let transcript = &genes[0].transcripts[0];
+        assert_eq!(range(&transcript.utr3), Some((143161, 143200)));
+        let (mut genes, _) = genes_from_gff(&gff);
+        let at_end = HashMap::from([(YeastChromosome::I, 143160)]);
+        infer_missing_utrs(&mut genes, UtrFallback::Median, 100, &at_end);
+        assert_eq!(genes[0].transcripts[0].utr3, None);
+    }
+
+    #[test]
+    fn intergenic_regions() {
+        let orf = |name: &str, strand: Strand, range: (usize, usize)| FeatureModel {
+            systematic_name: name.to_string(),
+            standard_name: name.to_string(),
+            ..model(strand, range, vec![range], None, None)
+        };
+        let models = vec![
+            orf("A", Strand::Plus, (10, 19)),
+            orf("B", Strand::Minus, (30, 39)),
+        ];
+        let translator =
+            Translator::from_offsets([(YeastChromosome::I, 5, 45), (YeastChromosome::II, 50, 10)]);
+
+        let mut annotations = Annotations::new(1);
+        annotations.set_regions(find_regions(&models, &translator, 3));
+        assert_eq!(annotations.column_names().len(), 12);
+
+        let mut cursor = annotations.cursor();
+        let regions = |row: Vec<std::borrow::Cow<str>>| -> Vec<String> {
+            row[6..].iter().map(|c| c.to_string()).collect()
+        };
+        assert_eq!(regions(cursor.row(4)), vec!["", "", "", "", "", ""]);
+        assert_eq!(
+            regions(cursor.row(7)),
+            vec!["Intergenic", "", "", "A", "3", "A"]
+        );
+        assert_eq!(regions(cursor.row(15)), vec!["", "", "", "", "", ""]);
+        assert_eq!(
+            regions(cursor.row(22)),
+            vec!["Intergenic", "A;B", "3;8", "", "", ""]
+        );
+        assert_eq!(
+            regions(cursor.row(41)),
+            vec!["Intergenic", "", "", "B", "2", "B"]
+        );
+        assert_eq!(
+            regions(cursor.row(49)),
+            vec!["Intergenic", "", "", "B", "10", ""]
+        );
+        assert_eq!(
+            regions(cursor.row(55)),
+            vec!["Intergenic", "", "", "", "", ""]
+        );
+    }
+
+    #[test]
+    fn genomic_columns() {
+        let translator =
+            Translator::from_offsets([(YeastChromosome::I, 0, 4), (YeastChromosome::II, 4, 6)]);
+        assert_eq!(translator.locate(5), Some((YeastChromosome::II, 2)));
+        assert_eq!(translator.locate(10), None);
+
+        let mut annotations = Annotations::new(1);
+        annotations
+            .track_mut(0)
+            .add_feature(2, 6, label("YBL001C", "", Strand::Minus));
+        annotations.set_coordinates(GenomeCoordinates::new(
+            &translator,
+            "ACGUACGUAC".to_string(),
+        ));
+        assert_eq!(annotations.track_columns().len(), 10);
+
+        let mut cursor = annotations.cursor();
+        assert_eq!(cursor.row(1)[6..], ["", "", "", ""]);
+        assert_eq!(cursor.row(2)[6..], ["chrI", "3", "G", "C"]);
+        assert_eq!(cursor.row(5)[6..], ["chrII", "2", "C", "G"]);
+        assert_eq!(cursor.row(7)[6..], ["", "", "", ""]);
+    }
+
+    #[test]
+    fn transcript_coordinates() {
+        let structure =
+            TranscriptStructure::new(Strand::Minus, vec![(10, 14), (20, 29)], Some((12, 25)));
+        assert_eq!(
+            structure.locate(29),
+            Some(TranscriptPosition::Exon {
+                number: 1,
+                index: 0
+            })
+        );
+        assert_eq!(
+            structure.locate(17),
+            Some(TranscriptPosition::Intron { number: 1 })
+        );
+        assert_eq!(structure.locate(30), None);
+        assert_eq!(structure.codon_offsets(29), Some((-4, -13)));
+        assert_eq!(structure.codon_offsets(25), Some((1, -9)));
+        assert_eq!(structure.codon_offsets(12), Some((9, -1)));
+        assert_eq!(structure.codon_offsets(10), Some((11, 2)));
+
+        let mut annotations = Annotations::new(1);
+        let track = annotations.track_mut(0);
+        track.add_feature(10, 29, label("YBL001C", "", Strand::Minus));
+        track.set_structure(structure);
+        annotations.set_transcript_columns(true);
+
+        let mut cursor = annotations.cursor();
+        assert_eq!(cursor.row(10)[6..], ["15", "11", "2", "Exon 2"]);
+        assert_eq!(cursor.row(17)[6..], ["", "", "", "Intron 1"]);
+        assert_eq!(cursor.row(25)[6..], ["5", "1", "-9", "Exon 1"]);
+
+        // Positions after the overlapping CDS pieces of a frameshift are not
+        // shifted by the shared bases.
+        let frameshift = FeatureModel {
+            introns: Vec::new(),
+            ..model(Strand::Plus, (0, 11), vec![(0, 5), (5, 11)], None, None)
+        };
+        let annotations = annotate(&[frameshift]).unwrap();
+        let mut cursor = annotations.cursor();
+        let (_, structure, _) = &cursor.features(11)[0];
+        let structure = structure.unwrap();
+        assert_eq!(
+            structure.locate(11),
+            Some(TranscriptPosition::Exon {
+                number: 1,
+                index: 11
+            })
+        );
+        assert_eq!(structure.codon_offsets(11).unwrap().0, 12);
+        assert_eq!(structure.position(11), Some(11));
+    }
+
+    #[test]
+    fn region_summary() {
+        assert!(gini(&[1.0, 1.0, 1.0]).abs() < 1e-6);
+        assert!((gini(&[0.0, 0.0, 0.0, 4.0]) - 0.75).abs() < 1e-6);
+
+        let model = model(
+            Strand::Plus,
+            (0, 9),
+            vec![(2, 3), (6, 7)],
+            Some((0, 1)),
+            Some((8, 9)),
+        );
+        let translator = Translator::from_offsets([(YeastChromosome::I, 0, 10)]);
+        let profile = [0.1, f32::NAN, 0.5, 1.5, 0.0, 0.0, 2.0, f32::NAN, 0.3, 0.3];
+        let summaries = summarize_model(&model, &translator, "GCAUAUGGAA", &profile).unwrap();
+
+        let regions: Vec<&str> = summaries.iter().map(|s| s.region.as_str()).collect();
+        assert_eq!(
+            regions,
+            vec!["UTR 5'", "CDS", "Exon 1", "Intron 1", "Exon 2", "UTR 3'"]
+        );
+        let cds = &summaries[1];
+        assert_eq!((cds.start, cds.end, cds.length), (3, 8, 4));
+        assert_eq!(cds.values, 3);
+        assert!((cds.gc_content - 0.5).abs() < 1e-6);
+        assert!((cds.coverage - 0.75).abs() < 1e-6);
+        assert!((cds.mean - 4.0 / 3.0).abs() < 1e-6);
+        assert_eq!(cds.median, 1.5);
+        assert_eq!(cds.max, 2.0);
+        assert_eq!(summaries[2].length, 4);
+    }
+
+    #[test]
+    fn metagene_landmarks() {
+        let plus = model(
+            Strand::Plus,
+            (0, 9),
+            vec![(2, 3), (6, 7)],
+            Some((0, 1)),
+            Some((8, 9)),
+        );
+        let minus = FeatureModel {
+            systematic_name: "YAL005C".to_string(),
+            standard_name: "SSA1".to_string(),
+            strand: Strand::Minus,
+            range: (10, 19),
+            exons: vec![(12, 17)],
+            introns: vec![],
+            utr5: Some((18, 19)),
+            utr3: Some((10, 11)),
+            ..plus.clone()
+        };
+        assert_eq!(Landmark::StartCodon.positions(&plus), vec![2]);
+        assert_eq!(Landmark::StopCodon.positions(&minus), vec![14]);
+        assert_eq!(Landmark::SpliceAcceptor.positions(&plus), vec![5]);
+        assert_eq!(Landmark::Tss.positions(&minus), vec![19]);
+
+        let profile: Vec<f32> = (0..20).map(|i| i as f32).collect();
+        let models = [plus, minus];
+        let curve = landmark_curve(
+            &models,
+            Landmark::StartCodon,
+            1,
+            GeneNormalisation::None,
+            &profile,
+        );
+        let means: Vec<f32> = curve.points.iter().map(|p| p.mean).collect();
+        assert_eq!(means, vec![(1.0 + 18.0) / 2.0, 9.5, (3.0 + 16.0) / 2.0]);
+        assert_eq!(curve.points[0].genes, 2);
+        assert!(curve.points[0].ci_low < curve.points[0].mean);
+
+        let curve = landmark_curve(
+            &models[..1],
+            Landmark::StartCodon,
+            3,
+            GeneNormalisation::Max,
+            &profile,
+        );
+        assert!(curve.points[0].mean.is_nan());
+        assert_eq!(curve.points[6].mean, 1.0);
+        assert!(curve.points[6].ci_low.is_nan() && curve.points[6].ci_high.is_nan());
+
+        // Isoforms of a gene count once.
+        let isoform = FeatureModel {
+            isoform: Some("TIF:1".to_string()),
+            ..models[0].clone()
+        };
+        let curve = landmark_curve(
+            &[models[0].clone(), isoform, models[1].clone()],
+            Landmark::StartCodon,
+            1,
+            GeneNormalisation::None,
+            &profile,
+        );
+        assert_eq!(curve.points[0].genes, 2);
+        assert_eq!(curve.points[0].mean, (1.0 + 18.0) / 2.0);
+
+        let curves = scaled_curves(&models, 2, GeneNormalisation::None, &profile);
+        let labels: Vec<&str> = curves.iter().map(|c| c.label.as_str()).collect();
+        assert_eq!(labels, vec!["UTR 5'", "CDS", "UTR 3'"]);
+        assert_eq!(curves[1].points[0].median, (2.5 + 16.0) / 2.0);
+    }
+
+    #[test]
+    fn transcript_export() {
+        let model = FeatureModel {
+            systematic_name: "YAL005C".to_string(),
+            standard_name: "SSA1".to_string(),
+            ..model(
+                Strand::Minus,
+                (0, 7),
+                vec![(2, 2), (5, 5)],
+                Some((6, 7)),
+                Some((0, 1)),
+            )
+        };
+        let profile = [0.0, 0.1, 0.2, 0.3, 0.4, 0.5, f32::NAN, 0.7];
+        let transcript = TranscriptProfile::new(&model, "AACGGUUC", &profile);
+        assert_eq!(transcript.id, "YAL005C_mRNA");
+        let isoform = FeatureModel {
+            isoform: Some("SGD:id001".to_string()),
+            ..model.clone()
+        };
+        let isoform = TranscriptProfile::new(&isoform, "AACGGUUC", &profile);
+        assert_eq!(isoform.file_stem(), "YAL005C_SGD_id001");
+        assert_eq!(transcript.sequence, "GAAGUU");
+        assert!(transcript.is_selected(&["SSA1".to_string()]));
+        assert!(!transcript.is_selected(&["YAL003W".to_string()]));
+
+        let mut shape = Vec::new();
+        ProfileFormat::Shape.write(&mut shape, &transcript).unwrap();
+        let shape = String::from_utf8(shape).unwrap();
+        assert!(shape.starts_with("1\t0.7000\n2\t-999\n3\t0.5000\n4\t0.2000\n"));
+
+        let mut map = Vec::new();
+        ProfileFormat::Map.write(&mut map, &transcript).unwrap();
+        assert!(String::from_utf8(map)
+            .unwrap()
+            .starts_with("1\t0.7000\t0\tG\n"));
+
+        let mut xml = Vec::new();
+        ProfileFormat::Xml.write(&mut xml, &transcript).unwrap();
+        let xml = String::from_utf8(xml).unwrap();
+        assert!(xml.contains("<sequence>GAAGTT</sequence>"));
+        assert!(xml.contains("<reactivity>0.700,NaN,0.500,0.200,0.100,0.000</reactivity>"));
+
+        // The bases shared by the CDS pieces of a frameshift are spliced once.
+        let frameshift = FeatureModel {
+            introns: Vec::new(),
+            ..self::model(Strand::Plus, (0, 7), vec![(0, 4), (4, 7)], None, None)
+        };
+        let transcript = TranscriptProfile::new(&frameshift, "AACGGUUC", &profile);
+        assert_eq!(transcript.sequence, "AACGGUUC");
+        assert_eq!(transcript.values.len(), 8);
+        assert_eq!(frameshift.blocks(), vec![(0, 7)]);
+    }
+
+    #[test]
+    fn normalisation() {
+        let values: Vec<f32> = (1..=100).map(|i| i as f32).collect();
+        let two_eight = Method::TwoEight.apply(&values);
+        assert!((two_eight[0] - 1.0 / 94.5).abs() < 1e-6);
+        let percentile = Method::Percentile90.apply(&values);
+        assert!((percentile[99] - 100.0 / 90.1).abs() < 1e-5);
+        let winsorised = Method::Winsorise.apply(&values);
+        assert_eq!(winsorised[99], 1.0);
+        assert!((winsorised[0] - 5.95 / 95.05).abs() < 1e-5);
+
+        let mut outliers = values.clone();
+        outliers[99] = 1000.0;
+        outliers[98] = f32::NAN;
+        let boxplot = Method::BoxPlot.apply(&outliers);
+        assert!(boxplot[98].is_nan());
+        assert!((boxplot[0] - 1.0 / 93.5).abs() < 1e-5);
+        assert!(Method::Percentile90.apply(&[0.0, 0.0])[0].is_nan());
+
+        let normalisation: Normalisation = "90th:chromosome".parse().unwrap();
+        assert_eq!(normalisation.scope, Scope::Chromosome);
+        assert_eq!(
+            normalisation.column_name(),
+            "Normalised 90th percentile (chromosome)"
+        );
+        assert!("2-8:gene".parse::<Normalisation>().is_err());
+
+        let translator =
+            Translator::from_offsets([(YeastChromosome::I, 0, 2), (YeastChromosome::II, 2, 2)]);
+        let profile = [1.0, 2.0, 4.0, 8.0];
+        let normalised = normalisation.apply(&profile, "ACAC", None, &translator, &[]);
+        assert_eq!(normalised, vec![1.0 / 1.9, 2.0 / 1.9, 4.0 / 7.6, 8.0 / 7.6]);
+        let genome: Normalisation = "90th".parse().unwrap();
+        let normalised = genome.apply(&profile, "ACAG", Some("a"), &translator, &[]);
+        assert!((normalised[2] - 4.0 / 3.7).abs() < 1e-6);
+        assert!(normalised[1].is_nan() && normalised[3].is_nan());
+    }
+
+    #[test]
+    fn probe_chemistry() {
+        assert_eq!("dms".parse::<Chemistry>().unwrap().reactive_bases(), "AC");
+        assert_eq!(
+            "gt".parse::<Chemistry>().unwrap(),
+            Chemistry::Custom("GU".to_string())
+        );
+        assert!("acx".parse::<Chemistry>().is_err());
+
+        let sequence = "ACGU";
+        let profile = [1.0, 2.0, 3.0, 4.0];
+        let mut probe = ProbeArgs {
+            chemistry: Chemistry::Dms,
+            flag_unreactive: false,
+        };
+        let masked = probe.apply(sequence, &profile);
+        assert_eq!(masked[..2], [1.0, 2.0]);
+        assert!(masked[2].is_nan() && masked[3].is_nan());
+        assert_eq!(
+            probe.chemistry.reactive(sequence),
+            vec![true, true, false, false]
+        );
+
+        probe.flag_unreactive = true;
+        assert_eq!(probe.apply(sequence, &profile), profile.to_vec());
+        assert_eq!(Chemistry::Shape.reactive(sequence), vec![true; 4]);
+    }
+
+    #[test]
+    fn replicate_merging() {
+        assert!(check_sequence("ACGU", "ACGU", "b").is_ok());
+        assert!(check_sequence("ACGU", "ACG", "b").is_err());
+        let error = check_sequence("ACGU", "ACCU", "b").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Replicate b differs from the input at line 4"
+        );
+
+        let a = vec![1.0, 2.0, f32::NAN, 4.0];
+        let b = vec![3.0, 4.0, f32::NAN, 8.0];
+        let c = vec![2.0, f32::NAN, f32::NAN, 1.0];
+        let merged = merge(&[a.clone(), b.clone(), c]);
+        assert_eq!(merged.count, vec![3, 2, 0, 3]);
+        assert_eq!(merged.mean[0], 2.0);
+        assert_eq!(merged.sd[0], 1.0);
+        assert!(merged.mean[2].is_nan() && merged.sd[2].is_nan());
+
+        assert!((pearson(&[1.0, 2.0, 3.0], &[2.0, 4.0, 6.0]) - 1.0).abs() < 1e-6);
+        assert!((spearman(&[1.0, 2.0, 3.0], &[1.0, 10.0, 100.0]) - 1.0).abs() < 1e-6);
+        assert!((spearman(&[1.0, 2.0, 3.0], &[3.0, 2.0, 1.0]) + 1.0).abs() < 1e-6);
+
+        let translator = Translator::from_offsets([(YeastChromosome::I, 0, 4)]);
+        let names = vec!["a".to_string(), "b".to_string()];
+        let correlations = correlations(&names, &[a, b], &translator, &[]);
+        assert_eq!(correlations.len(), 1);
+        assert_eq!(correlations[0].name, "chrI");
+        assert_eq!(correlations[0].positions, 3);
+        assert!((correlations[0].spearman - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn condition_comparison() {
+        let first =
+            Translator::from_offsets([(YeastChromosome::I, 0, 4), (YeastChromosome::II, 4, 2)]);
+        let second =
+            Translator::from_offsets([(YeastChromosome::II, 0, 2), (YeastChromosome::I, 2, 4)]);
+        let aligned = align(&first, &second, &[5.0, 6.0, 1.0, 2.0, 3.0, 4.0]).unwrap();
+        assert_eq!(aligned, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+        let shorter =
+            Translator::from_offsets([(YeastChromosome::I, 0, 4), (YeastChromosome::II, 4, 1)]);
+        assert!(align(&first, &shorter, &[0.0; 5]).is_err());
+
+        // Exact two-sided p-value is 0.0625; the normal approximation gives
+        // 0.0431.
+        let p = wilcoxon_signed_rank(&[1.0, 2.0, 3.0, 4.0, 5.0]);
+        assert!((p - 0.0431).abs() < 1e-3);
+        assert!((wilcoxon_signed_rank(&[1.0, -1.0]) - 1.0).abs() < 1e-6);
+
+        let adjusted = Correction::BenjaminiHochberg.adjust(&[0.01, 0.04, f32::NAN, 0.03]);
+        assert!((adjusted[0] - 0.03).abs() < 1e-6);
+        assert!((adjusted[1] - 0.04).abs() < 1e-6);
+        assert!((adjusted[3] - 0.04).abs() < 1e-6);
+        assert!(adjusted[2].is_nan());
+        assert_eq!(Correction::Bonferroni.adjust(&[0.5, 0.01]), vec![1.0, 0.02]);
+        let adjusted = Correction::Bonferroni.adjust(&[0.01, f32::NAN, 0.2]);
+        assert_eq!(adjusted[0], 0.02);
+        assert!(adjusted[1].is_nan());
+        assert_eq!(adjusted[2], 0.4);
+
+        let translator = Translator::from_offsets([(YeastChromosome::I, 0, 40)]);
+        let delta: Vec<f32> = (0..40)
+            .map(|i| {
+                if i < 20 {
+                    (i % 3) as f32 - 1.0
+                } else {
+                    1.0 + i as f32 / 100.0
+                }
+            })
+            .collect();
+        let windows = test_windows(&delta, &translator, 10, 5, 5, Correction::None);
+        assert_eq!(windows.len(), 7);
+        let hits = find_hits(&windows, &delta, 0.05);
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].start, hits[0].end, hits[0].windows), (15, 39, 4));
+        assert!(hits[0].mean_delta > 0.9);
+
+        let model = model(Strand::Plus, (30, 45), vec![(35, 45)], Some((30, 34)), None);
+        let mut hits = hits;
+        annotate_hits(&mut hits, &annotate(&[model]).unwrap());
+        assert_eq!(hits[0].features.len(), 1);
+        assert_eq!(hits[0].features[0].standard_name, "EFB1");
+        assert_eq!(
+            hits[0].features[0].subtypes,
+            vec![Subtype::Utr5, Subtype::Exon]
+        );
+    }
+
+    #[test]
+    fn profile_qc() {
+        let translator = Translator::from_offsets([(YeastChromosome::I, 0, 8)]);
+        let mut profile = vec![0.1, 0.2, f32::NAN, f32::NAN, f32::NAN, 0.3, 10.0, 0.2];
+        profile.extend([f32::NAN, 0.1]);
+        let model = model(Strand::Plus, (0, 3), vec![(0, 3)], None, None);
+        let report = qc_report(&profile, &translator, &[model], 2);
+
+        assert_eq!(report.positions, 10);
+        assert!((report.nan_fraction - 0.4).abs() < 1e-6);
+        assert_eq!(report.unassigned_positions, 2);
+        assert_eq!(report.unmapped_chromosomes.len(), 16);
+        assert!(!report.unmapped_chromosomes.contains(&"chrI".to_string()));
+        assert!((report.chromosomes[0].nan_fraction - 0.375).abs() < 1e-6);
+        assert_eq!(report.chromosomes[0].long_nan_runs, 1);
+        assert_eq!(report.feature_classes[0].category, "ORF");
+        assert!((report.feature_classes[0].nan_fraction - 0.5).abs() < 1e-6);
+        assert_eq!(report.distribution.values, 6);
+        assert_eq!(report.outliers.count, 1);
+        assert_eq!(report.outliers.highest[0].site.position, Some(7));
+        assert_eq!(report.long_nan_runs, 1);
+        assert_eq!(report.longest_nan_runs[0].length, 3);
+
+        let mut json = Vec::new();
+        write_qc_json(&mut json, &report).unwrap();
+        let json = String::from_utf8(json).unwrap();
+        assert!(json.contains("\"unmapped_chromosomes\""));
+        assert!(json.contains("\"chromosome\": \"chrI\""));
+        let mut summary = Vec::new();
+        write_qc_summary(&mut summary, &report).unwrap();
+        assert!(String::from_utf8(summary)
+            .unwrap()
+            .starts_with("10 positions, 40.0% NaN"));
+
+        let single = [f32::NAN, 0.5, f32::NAN];
+        let report = qc_report(&single, &translator, &[], 2);
+        assert_eq!(report.distribution.mean, Some(0.5));
+        assert_eq!(report.distribution.sd, None);
+        let empty = [f32::NAN; 3];
+        let report = qc_report(&empty, &translator, &[], 2);
+        assert_eq!(report.distribution.mean, None);
+        assert!(report.distribution.quantiles.is_empty());
+        assert_eq!(report.outliers.fence, None);
+        assert_eq!(report.outliers.fraction, 0.0);
+        let mut json = Vec::new();
+        write_qc_json(&mut json, &report).unwrap();
+        assert!(String::from_utf8(json).unwrap().contains("\"sd\": null"));
+        let mut summary = Vec::new();
+        write_qc_summary(&mut summary, &report).unwrap();
+        assert!(String::from_utf8(summary)
+            .unwrap()
+            .contains("Values: mean n/a, sd n/a"));
+    }
+
+    #[test]
+    fn feature_diagnostics() {
+        let gff = load_gff(Path::new("tests/sample.gff")).unwrap();
+        let (genes, diagnostics) = genes_from_gff(&gff);
+        assert!(diagnostics.is_empty());
+
+        let gene = |chromosome: YeastChromosome, start: usize, end: usize| Gene {
+            systematic_name: "YAL003W".to_string(),
+            standard_name: "EFB1".to_string(),
+            category: "ORF".to_string(),
+            qualifier: None,
+            coding: true,
+            chromosome,
+            strand: Strand::Plus,
+            start,
+            end,
+            transcripts: vec![Transcript {
+                isoform: Some("SGD:id001".to_string()),
+                exons: vec![Exon { start, end }],
+                introns: vec![],
+                utr5: Some(Utr {
+                    start: 1,
+                    end: start - 1,
+                    source: "SGD:id001".to_string(),
+                    inferred: false,
+                }),
+                utr3: Some(Utr {
+                    start: end + 1,
+                    end: end + 20,
+                    source: "SGD:id001".to_string(),
+                    inferred: false,
+                }),
+            }],
+        };
+        let translator = Translator::from_offsets([(YeastChromosome::I, 0, 100)]);
+        let layout = |gene: &Gene| {
+            let mut diagnostics = Vec::new();
+            let model = FeatureModel::from_transcript(
+                gene,
+                &gene.transcripts[0],
+                &translator,
+                &NoTrimming,
+                &[],
+                &mut diagnostics,
+            );
+            (model.map(|(model, _)| model), diagnostics)
+        };
+
+        let (model, diagnostics) = layout(&gene(YeastChromosome::I, 11, 90));
+        let model = model.unwrap();
+        assert_eq!(model.utr5, Some((0, 9)));
+        assert_eq!(model.utr3, None);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].reason, Reason::UtrOffEdge);
+        assert_eq!(diagnostics[0].isoform.as_deref(), Some("SGD:id001"));
+        assert_eq!(
+            diagnostics[0].detail,
+            "3' UTR 91-110 outside of chrI (1-100)"
+        );
+
+        let (model, diagnostics) = layout(&gene(YeastChromosome::I, 11, 101));
+        assert!(model.is_none());
+        assert_eq!(diagnostics[0].reason, Reason::OffEdge);
+        let (model, diagnostics) = layout(&gene(YeastChromosome::II, 11, 50));
+        assert!(model.is_none());
+        assert_eq!(diagnostics[0].reason.code(), "unmapped");
+
+        let mut records = gff.records.clone();
+        let gene_record = gff.roots().find(|r| r.kind == "gene").unwrap();
+        records.push(gene_record.clone());
+        let (duplicated, diagnostics) = genes_from_gff(&Gff::new(records));
+        assert_eq!(duplicated.len(), genes.len());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].systematic_name, genes[0].systematic_name);
+        assert_eq!(diagnostics[0].reason, Reason::Duplicate);
+
+        let mut records = gff.records.clone();
+        let mut plasmid = gene_record.clone();
+        plasmid.seqid = "2-micron".to_string();
+        plasmid
+            .attributes
+            .insert("ID".to_string(), "R0010W".to_string());
+        let mut anonymous = gene_record.clone();
+        anonymous.attributes.remove("ID");
+        records.extend([plasmid, anonymous]);
+        let (kept, diagnostics) = genes_from_gff(&Gff::new(records));
+        assert_eq!(kept.len(), genes.len());
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].systematic_name, "R0010W");
+        assert_eq!(diagnostics[0].reason, Reason::Unmapped);
+        assert_eq!(diagnostics[1].reason.code(), "no-id");
+        assert_eq!(diagnostics[1].detail, "gene at chrI:142174-143160");
+    }
+
+    #[test]
+    fn smoothing_and_gaps() {
+        let smoothing: Smoothing = "gaussian:9".parse().unwrap();
+        assert_eq!(smoothing.kernel, Kernel::Gaussian);
+        assert_eq!(smoothing.column_name(), "Smoothed Gaussian (9 nt)");
+        assert_eq!("median".parse::<Smoothing>().unwrap().window, 5);
+        assert!("mean:0".parse::<Smoothing>().is_err());
+        assert!("lowess".parse::<Smoothing>().is_err());
+
+        let segment = [1.0, 2.0, f32::NAN, 4.0, 9.0];
+        let mean: Smoothing = "mean:3".parse().unwrap();
+        let smoothed = mean.smooth(&segment);
+        assert_eq!(smoothed[0], 1.5);
+        assert!(smoothed[2].is_nan());
+        assert_eq!(smoothed[3], 6.5);
+        let median: Smoothing = "median:5".parse().unwrap();
+        assert_eq!(median.smooth(&segment)[4], 6.5);
+        let gaussian = Smoothing {
+            kernel: Kernel::Gaussian,
+            window: 5,
+        };
+        let smoothed = gaussian.smooth(&[0.0, 0.0, 3.0, 0.0, 0.0]);
+        assert!(smoothed[2] < 3.0 && smoothed[1] > smoothed[0]);
+
+        let gaps = [
+            1.0,
+            f32::NAN,
+            f32::NAN,
+            4.0,
+            f32::NAN,
+            f32::NAN,
+            f32::NAN,
+            0.0,
+        ];
+        let filled = fill_gaps(&gaps, 2);
+        assert_eq!(&filled[..4], &[1.0, 2.0, 3.0, 4.0]);
+        assert!(filled[4..7].iter().all(|x| x.is_nan()));
+        assert!(fill_gaps(&[f32::NAN, 1.0, f32::NAN], 5)
+            .iter()
+            .step_by(2)
+            .all(|x| x.is_nan()));
+
+        let model = FeatureModel {
+            qualifier: Some("Verified".to_string()),
+            ..model(Strand::Plus, (1, 8), vec![(1, 3), (6, 8)], None, None)
+        };
+        let profile = [5.0, 1.0, 1.0, 1.0, 7.0, 7.0, 3.0, f32::NAN, 3.0, 5.0];
+        let models = [model];
+        let smoothed = by_segment(&profile, &models, |s| mean.smooth(s));
+        assert!(smoothed[0].is_nan() && smoothed[9].is_nan());
+        assert_eq!(&smoothed[1..=6], &[1.0, 1.0, 1.0, 7.0, 7.0, 3.0]);
+        let filled = by_segment(&profile, &models, |s| fill_gaps(s, 1));
+        assert_eq!(filled[7], 3.0);
+    }
+
+    #[test]
+    fn structure_windows() {
+        assert_eq!(gini(&[0.0, 0.0, 0.0, 4.0]), 0.75);
+        assert_eq!(gini(&[1.0, 1.0, 1.0, 1.0]), 0.0);
+        assert!(gini(&[0.0, -1.0]).is_nan());
+        assert_eq!(entropy(&[1.0, 1.0, 1.0, 1.0]), 2.0);
+        assert_eq!(entropy(&[0.0, -1.0, 0.0, 4.0]), 0.0);
+        assert_eq!("high".parse::<Metric>().unwrap(), Metric::HighFraction);
+        assert!("max".parse::<Metric>().is_err());
+
+        let model = FeatureModel {
+            qualifier: Some("Verified".to_string()),
+            ..model(Strand::Plus, (0, 9), vec![(0, 3), (6, 9)], None, None)
+        };
+        let mut profile = vec![f32::NAN; 20];
+        for (i, value) in [0, 1, 2, 3, 6, 7, 8, 9].iter().zip(1..) {
+            profile[*i] = value as f32;
+        }
+        let transcript = TranscriptProfile::new(&model, &"A".repeat(20), &profile);
+        let options = WindowOptions {
+            size: 4,
+            step: 2,
+            min_values: 1,
+            high_reactivity: 5.0,
+        };
+        let windows = window_metrics(&transcript.values, &options);
+        assert_eq!(windows.len(), 3);
+        assert_eq!((windows[1].start, windows[1].end), (3, 6));
+        assert_eq!(windows[1].mean, 4.5);
+        assert_eq!(windows[2].high_fraction, 0.75);
+        let strict = WindowOptions {
+            min_values: 5,
+            ..options
+        };
+        assert!(window_metrics(&transcript.values, &strict)[0].gini.is_nan());
+
+        let translator = Translator::from_offsets([(YeastChromosome::I, 0, 20)]);
+        let bedgraph = |transcripts: &[(TranscriptProfile, Vec<WindowMetrics>)]| {
+            let mut buffer = Vec::new();
+            write_bedgraph(
+                &mut buffer,
+                transcripts,
+                Metric::Mean,
+                &options,
+                &translator,
+            )
+            .unwrap();
+            String::from_utf8(buffer).unwrap()
+        };
+        // Overlapping windows are averaged per position.
+        let single = [(transcript.clone(), windows.clone())];
+        let lines: Vec<String> = bedgraph(&single)
+            .lines()
+            .skip(1)
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                "chrI\t0\t2\t2.5000",
+                "chrI\t2\t4\t3.5000",
+                "chrI\t6\t8\t5.5000",
+                "chrI\t8\t10\t6.5000"
+            ]
+        );
+
+        // So are overlapping isoforms, leaving disjoint intervals.
+        let shifted: Vec<WindowMetrics> = windows
+            .iter()
+            .map(|w| WindowMetrics {
+                mean: w.mean + 1.0,
+                ..w.clone()
+            })
+            .collect();
+        let output = bedgraph(&[(transcript.clone(), windows), (transcript, shifted)]);
+        let records: Vec<(usize, usize)> = output
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let fields: Vec<&str> = line.split('\t').collect();
+                (fields[1].parse().unwrap(), fields[2].parse().unwrap())
+            })
+            .collect();
+        assert!(records.windows(2).all(|pair| pair[0].1 <= pair[1].0));
+        assert!(output.contains("chrI\t0\t2\t3.0000\n"));
+    }
+
+    #[test]
+    fn reactive_sites() {
+        assert_eq!("0.7".parse::<Threshold>().unwrap(), Threshold::Value(0.7));
+        assert_eq!(
+            "zscore:2".parse::<Threshold>().unwrap(),
+            Threshold::ZScore(2.0)
+        );
+        assert!("percentile:120".parse::<Threshold>().is_err());
+        assert!("sd:2".parse::<Threshold>().is_err());
+        let values = [1.0, 2.0, f32::NAN, 3.0, 4.0, 5.0];
+        assert_eq!(Threshold::Percentile(50.0).resolve(&values), 3.0);
+        let z = Threshold::ZScore(1.0).resolve(&values);
+        assert!((z - (3.0 + 2.5f32.sqrt())).abs() < 1e-5);
+
+        let model = FeatureModel {
+            qualifier: Some("Verified".to_string()),
+            ..model(
+                Strand::Plus,
+                (0, 19),
+                vec![(3, 8), (12, 17)],
+                Some((0, 2)),
+                Some((18, 19)),
+            )
+        };
+        let sequence = format!("GGGAUGCCCGUAGAAUAACC{}", "A".repeat(10));
+        let mut profile = vec![0.1; 30];
+        profile[6] = 0.9;
+        profile[8] = 1.0;
+        profile[13] = 0.8;
+        profile[25] = 2.0;
+        let translator = Translator::from_offsets([(YeastChromosome::I, 0, 30)]);
+
+        let mut sites = call_sites(&profile, 0.5, 1, 1, &translator);
+        assert_eq!(sites.len(), 3);
+        assert_eq!((sites[0].start, sites[0].end, sites[0].peak), (6, 8, 8));
+        assert_eq!(sites[0].reactive, 2);
+        assert_eq!(call_sites(&profile, 0.5, 0, 2, &translator).len(), 0);
+
+        annotate_sites(&mut sites, &annotate(&[model]).unwrap(), &sequence);
+        let feature = &sites[0].features[0];
+        assert_eq!(feature.subtypes, vec![Subtype::Exon]);
+        assert_eq!(feature.transcript_position, Some(9));
+        let codon = feature.codon.as_ref().unwrap();
+        assert_eq!((codon.number, codon.position), (2, 3));
+        assert_eq!(codon.sequence, "CCC");
+        assert_eq!(sites[1].features[0].codon.as_ref().unwrap().sequence, "GAA");
+        assert!(sites[2].features.is_empty());
+
+        let mut bed = Vec::new();
+        write_sites_bed(&mut bed, &sites, &translator).unwrap();
+        let bed = String::from_utf8(bed).unwrap();
+        let lines: Vec<&str> = bed.lines().collect();
+        assert_eq!(lines[0], "chrI\t6\t9\tsite_1\t500\t+");
+        assert_eq!(lines[2], "chrI\t25\t26\tsite_3\t1000\t.");
+
+        let mut table = Vec::new();
+        write_sites_table(&mut table, &sites, &translator).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert_eq!(
+            table.lines().nth(1).unwrap(),
+            "site_1\tchrI\t7\t9\t9\t1.0000\t0.6667\t2\tYAL003W\tExon\t9\t2\t3\tCCC"
+        );
+
+        let isoforms: Vec<FeatureModel> = [("a", 0), ("b", 3)]
+            .into_iter()
+            .map(|(isoform, tss)| FeatureModel {
+                isoform: Some(isoform.to_string()),
+                ..self::model(
+                    Strand::Plus,
+                    (tss, 19),
+                    vec![(3, 8), (12, 17)],
+                    (tss < 3).then_some((tss, 2)),
+                    Some((18, 19)),
+                )
+            })
+            .collect();
+        annotate_sites(&mut sites, &annotate(&isoforms).unwrap(), &sequence);
+        let ids: Vec<&str> = sites[0].features.iter().map(|f| f.id.as_str()).collect();
+        assert_eq!(ids, vec!["YAL003W#a", "YAL003W#b"]);
+        assert_eq!(sites[0].features[1].transcript_position, Some(6));
+        let mut table = Vec::new();
+        write_sites_table(&mut table, &sites, &translator).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert!(table
+            .lines()
+            .nth(1)
+            .unwrap()
+            .contains("\tYAL003W#a,YAL003W#b\tExon,Exon\t9,6\t"));
+    }
+
+    #[test]
+    fn many_overlapping_isoforms() {
+        let models: Vec<FeatureModel> = (0..14)
+            .map(|i| FeatureModel {
+                isoform: Some(format!("TIF:{}", i)),
+                ..model(
+                    Strand::Plus,
+                    (i, 100 + i),
+                    vec![(20, 80)],
+                    Some((i, 19)),
+                    Some((81, 100 + i)),
+                )
+            })
+            .collect();
+
+        let annotations = annotate(&models).unwrap();
+        assert_eq!(annotations.track_count(), 14);
+        let mut cursor = annotations.cursor();
+        let features = cursor.features(50);
+        assert_eq!(features.len(), 14);
+        assert!(features
+            .iter()
+            .all(|(label, _, subtypes)| label.systematic_name == "YAL003W"
+                && subtypes[0] == Subtype::Exon));
+    }
+
+    #[test]
+    fn frameshift_introns() {
+        let gff = load_gff(Path::new("tests/sample.gff")).unwrap();
+        let gene_records = |edit: &dyn Fn(&mut GffRecord) -> bool| {
+            let records = gff
+                .records
+                .iter()
+                .cloned()
+                .filter_map(|mut r| edit(&mut r).then_some(r))
+                .collect();
+            let (genes, diagnostics) = genes_from_gff(&Gff::new(records));
+            assert!(diagnostics.is_empty());
+            genes
+                .into_iter()
+                .find(|g| g.systematic_name == "YAL003W")
+                .unwrap()
+        };
+
+        // Overlapping CDS pieces without an annotated intron.
+        let gene = gene_records(&|r| {
+            if r.kind == "CDS" && r.start == 142620 {
+                r.start = 142250;
+            }
+            r.kind != "intron"
+        });
+        let transcript = &gene.transcripts[0];
+        assert_eq!(transcript.exons.len(), 2);
+        assert!(transcript.introns.is_empty());
+
+        // Touching pieces leave no gap either.
+        let gene = gene_records(&|r| {
+            if r.kind == "CDS" && r.start == 142620 {
+                r.start = 142254;
+            }
+            r.kind != "intron"
+        });
+        assert!(gene.transcripts[0].introns.is_empty());
+
+        // Annotated introns are taken as they are.
+        let gene = gene_records(&|r| {
+            if r.kind == "intron" {
+                r.start = 142300;
+            }
+            true
+        });
+        let intron = gene.transcripts[0].introns[0];
+        assert_eq!((intron.start, intron.end), (142300, 142619));
+    }
+
+    #[test]
+    fn shared_statistics() {
+        let values = sorted(&[3.0, f32::NAN, 1.0, 4.0, 2.0]);
+        assert_eq!(values, vec![1.0, 2.0, 3.0, 4.0]);
+        assert_eq!(mean(&values), 2.5);
+        assert_eq!(median(&values), 2.5);
+        assert_eq!(median(&values[..3]), 2.0);
+        assert!((quantile(&values, 0.9) - 3.7).abs() < 1e-6);
+        assert!((sd(&values).unwrap() - 1.290_994).abs() < 1e-5);
+        assert_eq!(sd(&values[..1]), None);
+        assert!(mean(&[]).is_nan() && median(&[]).is_nan());
+        assert_eq!(number(0.123_456), "0.1235");
+        assert_eq!(number(f32::NAN), "");
+    }
 }
@@ -0,0 +1,191 @@
+use std::{io::Write, str::FromStr};
+
+use anyhow::Result;
+
+use crate::{annotation::complement, model::FeatureModel, translator::Strand};
+
+/// Placeholder written by RNAstructure and ShapeMapper for missing values.
+const MISSING: &str = "-999";
+
+/// File format of an exported transcript profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfileFormat {
+    /// RNAstructure `.shape`: position and reactivity.
+    Shape,
+    /// ShapeMapper `.map`: position, reactivity, standard error and base.
+    Map,
+    /// RNA Framework XML.
+    Xml,
+    /// FASTA with the sequence on a single line, ready for a dot-bracket
+    /// line to be appended.
+    Fasta,
+}
+
+impl FromStr for ProfileFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shape" => Ok(Self::Shape),
+            "map" => Ok(Self::Map),
+            "xml" => Ok(Self::Xml),
+            "fasta" => Ok(Self::Fasta),
+            _ => Err(format!("Invalid profile format: {}", s)),
+        }
+    }
+}
+
+impl ProfileFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Shape => "shape",
+            Self::Map => "map",
+            Self::Xml => "xml",
+            Self::Fasta => "fa",
+        }
+    }
+
+    pub fn write<W: Write>(&self, writer: &mut W, transcript: &TranscriptProfile) -> Result<()> {
+        match self {
+            Self::Shape => write_shape(writer, transcript),
+            Self::Map => write_map(writer, transcript),
+            Self::Xml => write_xml(writer, transcript),
+            Self::Fasta => write_fasta(writer, transcript),
+        }
+    }
+}
+
+/// The mature transcript of a feature model, 5' to 3'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptProfile {
+    pub id: String,
+    pub systematic_name: String,
+    pub standard_name: String,
+    /// Spliced sense sequence.
+    pub sequence: String,
+    /// Profile values aligned with `sequence`, NaN where missing.
+    pub values: Vec<f32>,
+    /// Input index of every position of `sequence`.
+    pub indices: Vec<usize>,
+}
+
+impl TranscriptProfile {
+    /// Splices the blocks of `model`, including its refined UTRs, out of the
+    /// input `sequence` and `profile` and orients them 5' to 3'.
+    pub fn new(model: &FeatureModel, sequence: &str, profile: &[f32]) -> Self {
+        let mut indices: Vec<usize> = model
+            .blocks()
+            .into_iter()
+            .flat_map(|(start, end)| start..=end)
+            .collect();
+        if model.strand == Strand::Minus {
+            indices.reverse();
+        }
+
+        let base = |i: usize| sequence.get(i..=i).unwrap_or("N");
+        let sequence = indices
+            .iter()
+            .map(|i| match model.strand {
+                Strand::Plus => base(*i),
+                Strand::Minus => complement(base(*i)),
+            })
+            .collect();
+        let values = indices
+            .iter()
+            .map(|i| profile.get(*i).copied().unwrap_or(f32::NAN))
+            .collect();
+
+        Self {
+            id: model.transcript_id(),
+            systematic_name: model.systematic_name.clone(),
+            standard_name: model.standard_name.clone(),
+            sequence,
+            values,
+            indices,
+        }
+    }
+
+    /// Whether the transcript is one of `names`, by systematic or standard
+    /// name. An empty list selects every transcript.
+    pub fn is_selected(&self, names: &[String]) -> bool {
+        names.is_empty()
+            || names
+                .iter()
+                .any(|name| *name == self.systematic_name || *name == self.standard_name)
+    }
+
+    /// The id made safe as a file name: isoform ids such as
+    /// `YAL003W_SGD:id001` contain characters that are invalid on some file
+    /// systems or awkward in shells.
+    pub fn file_stem(&self) -> String {
+        self.id.replace([':', '/', '\\'], "_")
+    }
+}
+
+fn value(x: f32) -> String {
+    if x.is_nan() {
+        MISSING.to_string()
+    } else {
+        format!("{:.4}", x)
+    }
+}
+
+pub fn write_shape<W: Write>(writer: &mut W, transcript: &TranscriptProfile) -> Result<()> {
+    for (i, x) in transcript.values.iter().enumerate() {
+        writeln!(writer, "{}\t{}", i + 1, value(*x))?;
+    }
+    Ok(())
+}
+
+/// Writes a ShapeMapper `.map` file. Standard errors are not known and
+/// written as 0.
+pub fn write_map<W: Write>(writer: &mut W, transcript: &TranscriptProfile) -> Result<()> {
+    for (i, (x, base)) in transcript
+        .values
+        .iter()
+        .zip(transcript.sequence.chars())
+        .enumerate()
+    {
+        writeln!(writer, "{}\t{}\t0\t{}", i + 1, value(*x), base)?;
+    }
+    Ok(())
+}
+
+/// Writes an RNA Framework XML file. RNA Framework expects DNA bases and
+/// `NaN` for missing values.
+pub fn write_xml<W: Write>(writer: &mut W, transcript: &TranscriptProfile) -> Result<()> {
+    let values: Vec<String> = transcript
+        .values
+        .iter()
+        .map(|x| {
+            if x.is_nan() {
+                "NaN".to_string()
+            } else {
+                format!("{:.3}", x)
+            }
+        })
+        .collect();
+    writeln!(writer, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(writer, "<data>")?;
+    writeln!(
+        writer,
+        "\t<transcript id=\"{}\" length=\"{}\">",
+        transcript.id,
+        transcript.sequence.len()
+    )?;
+    writeln!(
+        writer,
+        "\t\t<sequence>{}</sequence>",
+        transcript.sequence.replace('U', "T")
+    )?;
+    writeln!(writer, "\t\t<reactivity>{}</reactivity>", values.join(","))?;
+    writeln!(writer, "\t</transcript>")?;
+    writeln!(writer, "</data>")?;
+    Ok(())
+}
+
+pub fn write_fasta<W: Write>(writer: &mut W, transcript: &TranscriptProfile) -> Result<()> {
+    writeln!(writer, ">{} {}", transcript.id, transcript.standard_name)?;
+    writeln!(writer, "{}", transcript.sequence)?;
+    Ok(())
+}
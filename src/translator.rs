@@ -52,6 +52,38 @@ impl FromStr for YeastChromosome {
     }
 }
 
+impl YeastChromosome {
+    pub const ALL: [YeastChromosome; 17] = [
+        Self::I,
+        Self::II,
+        Self::III,
+        Self::IV,
+        Self::V,
+        Self::VI,
+        Self::VII,
+        Self::VIII,
+        Self::IX,
+        Self::X,
+        Self::XI,
+        Self::XII,
+        Self::XIII,
+        Self::XIV,
+        Self::XV,
+        Self::XVI,
+        Self::Mito,
+    ];
+
+    /// Parses chromosome names as used by SGD, UCSC and NCBI files, e.g.
+    /// `chrIV`, `IV`, `chrmt` or `Mito`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.strip_prefix("chr").unwrap_or(name);
+        match name {
+            "mt" | "M" | "MT" | "Mito" => Some(Self::Mito),
+            _ => Self::from_str(name).ok(),
+        }
+    }
+}
+
 impl Display for YeastChromosome {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -83,8 +115,24 @@ pub struct GenomicRange {
     pub end: usize,
 }
 
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+pub enum Strand {
+    Plus,
+    Minus,
+}
+
+impl Display for Strand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plus => write!(f, "+"),
+            Self::Minus => write!(f, "-"),
+        }
+    }
+}
+
 pub struct Translator {
     mapping: HashMap<YeastChromosome, usize>,
+    lengths: HashMap<YeastChromosome, usize>,
 }
 
 impl Translator {
@@ -100,17 +148,32 @@ impl Translator {
             })
             .collect();
 
-        let mut vec: Vec<(YeastChromosome, usize)> = Vec::new();
+        let mut vec: Vec<(YeastChromosome, usize, usize)> = Vec::new();
         vec.par_extend(paths.par_iter().filter_map(|path| {
             debug!("Loading chromosome {}", path.display());
             let chromosome = load_fasta_gz(&path);
             let fasta = chromosome.iter().map(|(_, fasta)| fasta).next()?;
             let index = find_sequence(genome, &fasta)?;
-            Some((fasta.genomic_range().chromosome.clone(), index))
+            let range = fasta.genomic_range();
+            Some((range.chromosome, index, range.end))
         }));
 
-        let mapping: HashMap<YeastChromosome, usize> = vec.into_iter().collect();
-        Self { mapping }
+        Self::from_offsets(vec)
+    }
+
+    /// Builds a translator from known `(chromosome, offset, length)` triples,
+    /// where the offset is the input index of the chromosome's first
+    /// nucleotide.
+    pub fn from_offsets(
+        chromosomes: impl IntoIterator<Item = (YeastChromosome, usize, usize)>,
+    ) -> Self {
+        let mut mapping = HashMap::new();
+        let mut lengths = HashMap::new();
+        for (chromosome, offset, length) in chromosomes {
+            mapping.insert(chromosome.clone(), offset);
+            lengths.insert(chromosome, length);
+        }
+        Self { mapping, lengths }
     }
 
     pub fn translate_genomic_range(&self, range: &GenomicRange) -> Option<(usize, usize)> {
@@ -121,6 +184,50 @@ impl Translator {
         ))
     }
 
+    /// Returns the input index of the first nucleotide of `chromosome`.
+    pub fn chromosome_offset(&self, chromosome: &YeastChromosome) -> Option<usize> {
+        self.mapping.get(chromosome).copied()
+    }
+
+    /// All mapped chromosomes as `(chromosome, offset, length)`, ordered by
+    /// their position in the input.
+    pub fn chromosomes(&self) -> Vec<(YeastChromosome, usize, usize)> {
+        let mut chromosomes: Vec<(YeastChromosome, usize, usize)> = self
+            .mapping
+            .iter()
+            .map(|(chromosome, offset)| {
+                let length = self.lengths.get(chromosome).copied().unwrap_or(0);
+                (chromosome.clone(), *offset, length)
+            })
+            .collect();
+        chromosomes.sort_by_key(|(_, offset, _)| *offset);
+        chromosomes
+    }
+
+    /// Maps an input index back to a chromosome and 1-based position.
+    pub fn locate(&self, index: usize) -> Option<(YeastChromosome, usize)> {
+        self.mapping
+            .iter()
+            .find(|(chromosome, offset)| {
+                let length = self.lengths.get(chromosome).copied().unwrap_or(0);
+                **offset <= index && index < **offset + length
+            })
+            .map(|(chromosome, offset)| (chromosome.clone(), index - offset + 1))
+    }
+
+    /// Chromosomes that were not found in the input.
+    pub fn unmapped(&self) -> Vec<YeastChromosome> {
+        YeastChromosome::ALL
+            .iter()
+            .filter(|chromosome| !self.mapping.contains_key(chromosome))
+            .cloned()
+            .collect()
+    }
+
+    pub fn chromosome_length(&self, chromosome: &YeastChromosome) -> Option<usize> {
+        self.lengths.get(chromosome).copied()
+    }
+
     pub fn translate_nt(&self, chromosome: &YeastChromosome, index: usize) -> Option<usize> {
         let base = self.mapping.get(chromosome)?;
         Some(base + index - 1)
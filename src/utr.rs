@@ -0,0 +1,215 @@
+use std::io::Write;
+
+use anyhow::Result;
+
+use crate::translator::Strand;
+
+/// A UTR in input index space, before refinement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UtrRegion {
+    pub start: usize,
+    pub end: usize,
+    pub is_5p: bool,
+    pub strand: Strand,
+}
+
+impl UtrRegion {
+    /// Whether the end away from the CDS is `start`, i.e. refinement moves
+    /// `start` and keeps `end`.
+    fn extends_left(&self) -> bool {
+        self.is_5p == (self.strand == Strand::Plus)
+    }
+
+    /// Positions from the CDS-proximal end towards the distal one.
+    fn positions(&self) -> Box<dyn Iterator<Item = usize>> {
+        if self.extends_left() {
+            Box::new((self.start..=self.end).rev())
+        } else {
+            Box::new(self.start..=self.end)
+        }
+    }
+
+    /// Profile values of the `window` positions just beyond `i` in the distal
+    /// direction. Positions outside the profile count as NaN.
+    fn beyond(&self, i: usize, window: usize, profile: &[f32]) -> Vec<f32> {
+        (1..=window)
+            .map(|k| {
+                let j = if self.extends_left() {
+                    i.checked_sub(k)
+                } else {
+                    Some(i + k)
+                };
+                j.and_then(|j| profile.get(j).copied()).unwrap_or(f32::NAN)
+            })
+            .collect()
+    }
+
+    /// The UTR cut right after `i`, keeping the CDS-proximal part.
+    fn cut_at(&self, i: usize) -> (usize, usize) {
+        if self.extends_left() {
+            (i, self.end)
+        } else {
+            (self.start, i)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UtrRefinement {
+    /// Refined range or `None` when the UTR is discarded.
+    pub range: Option<(usize, usize)>,
+    pub reason: String,
+}
+
+/// Decides where a UTR really ends given the reactivity profile.
+pub trait UtrBoundaryStrategy: Send + Sync {
+    fn refine(&self, utr: &UtrRegion, profile: &[f32]) -> UtrRefinement;
+}
+
+/// Keeps UTRs exactly as annotated.
+pub struct NoTrimming;
+
+impl UtrBoundaryStrategy for NoTrimming {
+    fn refine(&self, utr: &UtrRegion, _profile: &[f32]) -> UtrRefinement {
+        UtrRefinement {
+            range: Some((utr.start, utr.end)),
+            reason: "kept as annotated".to_string(),
+        }
+    }
+}
+
+/// Cuts a UTR at the first run of `window` NaN values beyond it. UTRs without
+/// such a run are discarded. With a window of 6 this is the annotator's
+/// original behaviour.
+pub struct NanRun {
+    pub window: usize,
+}
+
+impl Default for NanRun {
+    fn default() -> Self {
+        Self { window: 6 }
+    }
+}
+
+impl UtrBoundaryStrategy for NanRun {
+    fn refine(&self, utr: &UtrRegion, profile: &[f32]) -> UtrRefinement {
+        for i in utr.positions() {
+            if utr
+                .beyond(i, self.window, profile)
+                .iter()
+                .all(|x| x.is_nan())
+            {
+                return UtrRefinement {
+                    range: Some(utr.cut_at(i)),
+                    reason: format!("run of {} NaN values", self.window),
+                };
+            }
+        }
+        UtrRefinement {
+            range: None,
+            reason: format!("no run of {} NaN values", self.window),
+        }
+    }
+}
+
+/// Cuts a UTR where the fraction of non-NaN values in the next `window`
+/// positions drops below `min_fraction`.
+pub struct CoverageThreshold {
+    pub window: usize,
+    pub min_fraction: f32,
+}
+
+impl UtrBoundaryStrategy for CoverageThreshold {
+    fn refine(&self, utr: &UtrRegion, profile: &[f32]) -> UtrRefinement {
+        for i in utr.positions() {
+            let values = utr.beyond(i, self.window, profile);
+            let covered = values.iter().filter(|x| !x.is_nan()).count();
+            if (covered as f32) < self.min_fraction * values.len() as f32 {
+                return UtrRefinement {
+                    range: Some(utr.cut_at(i)),
+                    reason: format!("coverage below {}", self.min_fraction),
+                };
+            }
+        }
+        UtrRefinement {
+            range: Some((utr.start, utr.end)),
+            reason: "coverage never below threshold".to_string(),
+        }
+    }
+}
+
+/// Cuts a UTR where the mean signal of the next `window` positions falls
+/// below `ratio` times the mean over the whole UTR. NaN counts as no signal.
+pub struct SignalDropOff {
+    pub window: usize,
+    pub ratio: f32,
+}
+
+impl UtrBoundaryStrategy for SignalDropOff {
+    fn refine(&self, utr: &UtrRegion, profile: &[f32]) -> UtrRefinement {
+        let signal = |x: &f32| if x.is_nan() { 0.0 } else { *x };
+        let reference: f32 = (utr.start..=utr.end)
+            .map(|i| profile.get(i).map_or(0.0, signal))
+            .sum::<f32>()
+            / (utr.end - utr.start + 1) as f32;
+
+        if reference <= 0.0 {
+            return UtrRefinement {
+                range: None,
+                reason: "no signal".to_string(),
+            };
+        }
+
+        for i in utr.positions() {
+            let values = utr.beyond(i, self.window, profile);
+            let mean = values.iter().map(signal).sum::<f32>() / values.len() as f32;
+            if mean < self.ratio * reference {
+                return UtrRefinement {
+                    range: Some(utr.cut_at(i)),
+                    reason: format!("signal below {} of UTR mean", self.ratio),
+                };
+            }
+        }
+        UtrRefinement {
+            range: Some((utr.start, utr.end)),
+            reason: "signal never dropped".to_string(),
+        }
+    }
+}
+
+/// One line of the UTR refinement report.
+#[derive(Debug, Clone)]
+pub struct UtrReport {
+    pub systematic_name: String,
+    /// Dataset and record the UTR comes from, e.g. `SGD:id001`.
+    pub source: String,
+    pub utr: UtrRegion,
+    pub refinement: UtrRefinement,
+}
+
+pub fn write_utr_report<W: Write>(writer: &mut W, reports: &[UtrReport]) -> Result<()> {
+    writeln!(
+        writer,
+        "Systematic name\tSource\tUTR\tStrand\tOriginal start\tOriginal end\tRefined start\tRefined end\tReason"
+    )?;
+    for report in reports {
+        let (start, end) = match report.refinement.range {
+            Some((start, end)) => (start.to_string(), end.to_string()),
+            None => (String::new(), String::new()),
+        };
+        writeln!(
+            writer,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            report.systematic_name,
+            report.source,
+            if report.utr.is_5p { "5'" } else { "3'" },
+            report.utr.strand,
+            report.utr.start,
+            report.utr.end,
+            start,
+            end,
+            report.refinement.reason
+        )?;
+    }
+    Ok(())
+}